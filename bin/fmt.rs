@@ -24,6 +24,7 @@
 
 use std::env;
 use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
 
 #[cfg(not(target_os = "windows"))]
 use ansi_term::ANSIString;
@@ -37,6 +38,36 @@ pub enum ColorWhen {
     Never,
 }
 
+impl ColorWhen {
+    /// Names accepted by [`ColorWhen::from_name`].
+    pub const NAMES: &'static [&'static str] = &["auto", "always", "never"];
+
+    pub fn from_name(name: &str) -> Option<ColorWhen> {
+        match name {
+            "auto" => Some(ColorWhen::Auto),
+            "always" => Some(ColorWhen::Always),
+            "never" => Some(ColorWhen::Never),
+            _ => None,
+        }
+    }
+}
+
+/// The setting used by `Colorizer::default`, as chosen on the command line.
+static DEFAULT_COLOR_WHEN: AtomicU8 = AtomicU8::new(ColorWhen::Auto as u8);
+
+/// Sets when the colorizers returned by `Colorizer::default` use colors.
+pub fn set_default_color_when(when: ColorWhen) {
+    DEFAULT_COLOR_WHEN.store(when as u8, Ordering::Relaxed);
+}
+
+fn default_color_when() -> ColorWhen {
+    match DEFAULT_COLOR_WHEN.load(Ordering::Relaxed) {
+        x if x == ColorWhen::Always as u8 => ColorWhen::Always,
+        x if x == ColorWhen::Never as u8 => ColorWhen::Never,
+        _ => ColorWhen::Auto,
+    }
+}
+
 pub fn is_a_tty(stderr: bool) -> bool {
     let stream = if stderr { atty::Stream::Stderr } else { atty::Stream::Stdout };
     atty::is(stream)
@@ -109,7 +140,7 @@ impl Colorizer {
 
 impl Default for Colorizer {
     fn default() -> Self {
-        Colorizer::new(ColorizerOption { use_stderr: true, when: ColorWhen::Auto })
+        Colorizer::new(ColorizerOption { use_stderr: true, when: default_color_when() })
     }
 }
 
@@ -171,6 +202,26 @@ pub fn print_error(err: &anyhow::Error, c: &Colorizer) {
     err.chain().skip(1).for_each(|cause| eprintln!("       cause: {}", cause));
 }
 
+pub fn print_warning(msg: &str, c: &Colorizer) {
+    eprintln!("{} {}", c.warning("warning:"), msg);
+}
+
+/// Prints an error as JSON. `error` is the message of the [`CliptoolsError`] carried by `err`, and
+/// `causes` holds the rest of the chain, as printed in "cause:" lines by [`print_error`].
+///
+/// [`CliptoolsError`]: cliptools::CliptoolsError
+pub fn print_error_json(err: &anyhow::Error, error: &cliptools::CliptoolsError) {
+    let message = error.to_string();
+    let causes = err.chain().map(|cause| cause.to_string()).filter(|cause| *cause != message);
+    let error = serde_json::json!({
+        "version": cliptools::JSON_VERSION,
+        "error": message,
+        "causes": causes.collect::<Vec<_>>(),
+        "code": error.exit_code(),
+    });
+    eprintln!("{}", error);
+}
+
 #[cfg(all(test, not(target_os = "windows")))]
 mod test {
     use ansi_term::ANSIString;
@@ -1,25 +1,93 @@
 mod fmt;
+mod process;
 
 use std::array::IntoIter;
-use std::collections::HashMap;
-use std::fmt::Formatter;
-use std::io::{Read, Write};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
-use arboard::{Clipboard, ContentType};
-use clap::{App, Arg, ArgGroup, ArgMatches, SubCommand};
-use thiserror::Error;
+use arboard::ContentType;
+use chrono::Local;
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+use cliptools::aliases::Aliases;
+use cliptools::backoff::{self, Backoff};
+use cliptools::escape::{self, Syntax};
+use cliptools::hash::{self, HashAlgorithm};
+use cliptools::markup::HtmlSelection;
+use cliptools::selftest::{self, Outcome};
+use cliptools::text::{self, Case, LineEnding, LineRange};
+use cliptools::{encoding, files, framed, images, markup, sniff, table, urls, watch};
+use cliptools::{
+    extension_to_ct, format_size, is_textual, normalize_system_type, show_content, show_ct,
+    show_string, string_to_ct, Category, ClipboardBackend, CliptoolsError, Selection, Stats,
+    SystemClipboard,
+};
+use indexmap::IndexMap;
 
-use crate::fmt::{is_a_tty, print_error, Colorizer};
+use crate::fmt::{is_a_tty, print_error, print_error_json, print_warning, ColorWhen, Colorizer};
 
 const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
+const TARGET: Option<&'static str> = option_env!("CLIPTOOLS_TARGET");
+const GIT_COMMIT: Option<&'static str> = option_env!("CLIPTOOLS_GIT_COMMIT");
 
-pub fn main() {
-    human_panic::setup_panic!();
+/// Maximum output of `paste --repeat`, to avoid filling disks by mistake.
+const MAX_REPEAT_OUTPUT: usize = 1 << 30;
+/// Default size of the buffer in front of stdout for `paste`.
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
 
-    #[rustfmt::skip]
-    let matches = App::new("cliptools")
+/// Builds the command line parser.
+#[rustfmt::skip]
+fn build_app() -> App<'static, 'static> {
+    App::new("cliptools")
         .version(VERSION.unwrap_or("unknown"))
+        .arg(Arg::with_name("error-format")
+            .help("Format used to report errors on stderr. `json` prints an object of the form \
+                   {\"version\": 1, \"error\": \"...\", \"causes\": [\"...\"], \"code\": N} \
+                   for consumption by wrapper scripts, where `causes` details the error.")
+            .long("error-format")
+            .takes_value(true)
+            .possible_values(&["human", "json"])
+            .default_value("human"))
+        .arg(Arg::with_name("color")
+            .help("When to use colors in messages on stderr. Defaults to `auto`, which uses \
+                   colors if stderr is a terminal.")
+            .long("color")
+            .takes_value(true)
+            .value_name("WHEN")
+            .possible_values(ColorWhen::NAMES))
+        .arg(Arg::with_name("mono")
+            .help("Never use colors, like `--color never`")
+            .long("mono"))
+        .group(ArgGroup::with_name("colors")
+            .args(&["color", "mono"]))
+        .arg(Arg::with_name("alias")
+            .help("Define an alias for a system type for this invocation, e.g. \
+                   `--alias shot=image/png`. Overrides aliases from the config file and builtin \
+                   aliases. Can be repeated.")
+            .long("alias")
+            .takes_value(true)
+            .value_name("NAME=TYPE")
+            .multiple(true)
+            .number_of_values(1))
+        .arg(Arg::with_name("clipboard")
+            .help("Clipboard to operate on. The primary and secondary selections exist on X11, \
+                   and the primary one on some Wayland compositors; they only hold text.")
+            .long("clipboard")
+            .takes_value(true)
+            .value_name("NAME")
+            .possible_values(Selection::NAMES)
+            .default_value("clipboard"))
+        .arg(Arg::with_name("backoff-on-busy")
+            .help("If the clipboard is busy, e.g. because another program holds the X11 \
+                   selection, retry a few times, waiting longer after each attempt. Other errors \
+                   are reported immediately.")
+            .long("backoff-on-busy"))
+        .arg(Arg::with_name("version-json")
+            .help("Prints version information as JSON")
+            .long("version-json"))
         .subcommand(SubCommand::with_name("paste").about("Prints data from clipboard")
             .arg(Arg::with_name("no-newline")
                 .help("Don't append a trailing newline even if the contents of the clipboard \
@@ -29,7 +97,9 @@ pub fn main() {
             .arg(Arg::with_name("type")
                 .help("Format to fetch the data in, if available. Must be one of `url`, `html`, \
                        `pdf`, `png`, `rtf`, or `text`. For other formats, use --system-type, \
-                       or prefix your type with an at sign (@).")
+                       or prefix your type with an at sign (@). On Windows and macOS, `files` \
+                       stands for the list of copied files, one path per line. `list` prints \
+                       the types available instead, like `list-types`.")
                 .long("type")
                 .short("t")
                 .takes_value(true))
@@ -39,25 +109,421 @@ pub fn main() {
                        dependent; for a portable alternative, use --type.")
                 .long("system-type")
                 .takes_value(true))
+            .arg(Arg::with_name("system-type-ci")
+                .help("Normalize the case of --system-type according to the platform's \
+                       conventions, e.g. `Text/HTML` becomes `text/html`, so that types typed \
+                       with the wrong case are still found")
+                .long("system-type-ci")
+                .requires("system-type"))
+            .arg(Arg::with_name("count-formats")
+                .help("Print the number of distinct formats in the clipboard instead of any data, \
+                       like `list-types --count`")
+                .long("count-formats")
+                .conflicts_with_all(&["type", "system-type", "types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("list-types")
+                .help("Print the types available instead of any data, like `--type list` and the \
+                       list-types subcommand")
+                .long("list-types")
+                .conflicts_with_all(&[
+                    "type", "system-type", "types", "jsonl", "image", "watch", "count-formats",
+                ]))
+            .arg(Arg::with_name("table")
+                .help("Print the first HTML table in the clipboard as tab-separated text, as \
+                       copied from spreadsheets. Falls back to the plain text if there is none.")
+                .long("table")
+                .conflicts_with_all(&["type", "system-type", "types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("interactive")
+                .help("List the formats in the clipboard and ask which one to print. Requires a \
+                       terminal.")
+                .long("interactive")
+                .short("i")
+                .conflicts_with_all(&["type", "system-type", "types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("types")
+                .help("Comma-separated list of formats to print one after the other, separated by \
+                       --separator. Formats missing from the clipboard are skipped.")
+                .long("types")
+                .takes_value(true)
+                .use_delimiter(true))
+            .arg(Arg::with_name("separator")
+                .help("Separator printed between the formats requested with --types (see \
+                       --record-separator), or between repetitions with --repeat (defaults to \
+                       nothing). Recognizes the escapes \\0, \\t, \\n and \\f.")
+                .long("separator")
+                .takes_value(true))
+            .arg(Arg::with_name("record-separator")
+                .help("Separator printed between the formats requested with --types. `auto`, the \
+                       default unless --separator is given, uses NUL characters if binary output \
+                       is allowed and newlines otherwise; `custom` uses --separator. With NUL \
+                       separators, no trailing newline is added.")
+                .long("record-separator")
+                .takes_value(true)
+                .possible_values(&["auto", "nul", "newline", "custom"])
+                .requires("types")
+                .requires_if("custom", "separator"))
+            .arg(Arg::with_name("repeat")
+                .help("Print the data N times")
+                .long("repeat")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with_all(&["types", "hash", "count"]))
+            .arg(Arg::with_name("jsonl")
+                .help("Print every format in the clipboard as JSON Lines, i.e. one object of the \
+                       form {\"version\": 1, \"type\": ..., \"content\": ...} per \
+                       line. Data that is not valid \
+                       UTF-8 is base64-encoded, and has an additional \"encoding\": \"base64\" \
+                       key.")
+                .long("jsonl")
+                .conflicts_with_all(&["hash", "count", "repeat", "image", "watch-once"]))
             .group(ArgGroup::with_name("format")
-                .args(&["type", "system-type"]))
+                .args(&["type", "system-type", "types", "jsonl"]))
+            .arg(Arg::with_name("decompress")
+                .help("Decompress gzip data, as copied with `copy --compress`, before processing \
+                       it")
+                .long("decompress")
+                .conflicts_with_all(&["types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("annotate")
+                .help("Also print a JSON object describing the data on stderr, with its type, \
+                       size in bytes, encoding and whether it is valid UTF-8. Not printed with \
+                       --quiet.")
+                .long("annotate")
+                .conflicts_with_all(&["types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("ensure-type")
+                .help("Check that the data looks like the given format (e.g. that `png` data \
+                       starts with the PNG signature) before printing it, and fail otherwise")
+                .long("ensure-type")
+                .takes_value(true)
+                .value_name("TYPE")
+                .conflicts_with_all(&["types", "jsonl", "image"]))
+            .arg(Arg::with_name("require-utf8")
+                .help("Fail before printing anything if the data is not valid UTF-8, even with \
+                       --binary. Options that process text, such as --json-pretty, already \
+                       behave this way.")
+                .long("require-utf8")
+                .conflicts_with_all(&["jsonl", "image"]))
+            .arg(Arg::with_name("owner-info")
+                .help("Print the application owning the clipboard on stderr, or `unknown` if the \
+                       clipboard backend doesn't tell (only Windows does)")
+                .long("owner-info"))
+            .arg(Arg::with_name("charset-detect")
+                .help("Guess the character encoding of the data, and print it on stderr. Only \
+                       supported for textual types. This helps understand garbled text copied \
+                       by applications that don't use UTF-8.")
+                .long("charset-detect")
+                .conflicts_with_all(&["types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("transcode")
+                .help("With --charset-detect, convert the data from the detected encoding to \
+                       UTF-8")
+                .long("transcode")
+                .requires("charset-detect"))
+            .arg(Arg::with_name("ascii-only")
+                .help("Fail if the data contains any non-ASCII byte")
+                .long("ascii-only")
+                .conflicts_with_all(&["types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("validate")
+                .help("Check that the data is well-formed instead of printing it, exiting with \
+                       code 1 if it isn't. Supports text (valid UTF-8), png (chunk structure) \
+                       and types with `json` in their name.")
+                .long("validate")
+                .conflicts_with_all(&["types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("strict-type")
+                .help("Only accept aliases in --type and --types, rejecting custom types prefixed \
+                       with an at sign (@), to catch typos")
+                .long("strict-type")
+                .conflicts_with("system-type"))
             .arg(Arg::with_name("binary")
-                .help("Allow binary output. By default, this is disallowed if the output is a \
-                       terminal, and disallowed otherwise.")
+                .help("Allow binary output. `--binary` alone is the same as `--binary always`. \
+                       If not given, binary output is allowed, unless the CLIPTOOLS_ALLOW_BINARY \
+                       environment variable is set to a false value (0, false, no, off), in \
+                       which case it is disallowed if the output is a terminal.")
                 .long("binary")
                 .min_values(0)
                 .max_values(1)
-                .possible_values(&["auto", "always", "never"])))
+                .possible_values(&["auto", "always", "never"]))
+            .arg(Arg::with_name("force-binary-detection")
+                .help("Also treat contents as binary if they are valid UTF-8 but contain NUL or \
+                       many other control characters, which could garble a terminal.")
+                .long("force-binary-detection"))
+            .arg(Arg::with_name("warn-binary")
+                .help("Print a warning on stderr when printing data that is not valid UTF-8, \
+                       because binary output is allowed")
+                .long("warn-binary"))
+            .arg(Arg::with_name("fail-if-empty")
+                .help("Exit with an error if the requested data is present but empty, instead of \
+                       printing nothing. With --types, fail if all of the types are empty.")
+                .long("fail-if-empty"))
+            .arg(Arg::with_name("as-text")
+                .help("Convert `html` or `rtf` data to plain text by stripping markup. Has no \
+                       effect for other types.")
+                .long("as-text"))
+            .arg(Arg::with_name("as-html")
+                .help("Convert `rtf` data to basic HTML, keeping paragraphs and bold, italic and \
+                       underlined text. Only supported for the `rtf` type.")
+                .long("as-html")
+                .conflicts_with_all(&["as-text", "types", "jsonl", "image"]))
+            .arg(Arg::with_name("select")
+                .help("Only print the text of the elements matching the given CSS selector in \
+                       `html` data, one per line")
+                .long("select")
+                .takes_value(true)
+                .value_name("CSS")
+                .conflicts_with("as-text"))
+            .arg(Arg::with_name("outer")
+                .help("With --select, print the elements' markup instead of their text")
+                .long("outer")
+                .requires("select"))
+            .arg(Arg::with_name("attr")
+                .help("With --select, print the given attribute of the elements (e.g. `href`) \
+                       instead of their text")
+                .long("attr")
+                .takes_value(true)
+                .value_name("NAME")
+                .requires("select")
+                .conflicts_with("outer"))
+            .arg(Arg::with_name("text-fallback")
+                .help("If plain text is requested but the clipboard has none, derive it from HTML \
+                       or RTF data by stripping markup")
+                .long("text-fallback"))
+            .arg(Arg::with_name("url-decode")
+                .help("Decode percent-encoded characters in `url` data. Has no effect for other \
+                       types.")
+                .long("url-decode"))
+            .arg(Arg::with_name("normalize-url")
+                .help("Canonicalize `url` data: lowercase the scheme and host, remove default \
+                       ports and resolve `.` and `..` path segments. Has no effect for other \
+                       types.")
+                .long("normalize-url"))
+            .arg(Arg::with_name("image")
+                .help("Save the image in the clipboard to the given file, in the format given by \
+                       its extension (.png, .jpg, .bmp...)")
+                .long("image")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with_all(&["type", "system-type", "types"]))
+            .arg(Arg::with_name("hash")
+                .help("Print the hex digest of the data instead of the data itself")
+                .long("hash")
+                .takes_value(true)
+                .value_name("ALGO")
+                .possible_values(HashAlgorithm::NAMES))
+            .arg(Arg::with_name("grep")
+                .help("Only print the lines of text matching the regular expression, and exit \
+                       with code 1 if none does")
+                .long("grep")
+                .takes_value(true)
+                .value_name("REGEX")
+                .conflicts_with_all(&["types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("invert")
+                .help("With --grep, print the lines that don't match instead")
+                .long("invert")
+                .requires("grep"))
+            .arg(Arg::with_name("ignore-case")
+                .help("With --grep, ignore case when matching")
+                .long("ignore-case")
+                .requires("grep"))
+            .arg(Arg::with_name("expand-tabs")
+                .help("Replace tabs in text with spaces, with tab stops every N columns")
+                .long("expand-tabs")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with_all(&["types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("unexpand-tabs")
+                .help("Replace runs of spaces in text with tabs, with tab stops every N columns")
+                .long("unexpand-tabs")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with_all(&["expand-tabs", "types", "jsonl", "image", "watch"]))
+            .arg(Arg::with_name("head")
+                .help("Only print the first N lines of text")
+                .long("head")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with_all(&["line", "tail"]))
+            .arg(Arg::with_name("tail")
+                .help("Only print the last N lines of text")
+                .long("tail")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("line"))
+            .arg(Arg::with_name("preview")
+                .help("Print the first N bytes of the data, whatever its type, with non-printable \
+                       characters shown in caret notation (like `cat -v`)")
+                .long("preview")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with_all(&["hash", "count", "repeat", "types", "jsonl", "image"]))
+            .arg(Arg::with_name("count")
+                .help("Print the number of bytes, characters, lines or words in the data instead \
+                       of the data itself. Counts other than bytes require text data.")
+                .long("count")
+                .takes_value(true)
+                .possible_values(&["bytes", "chars", "lines", "words"])
+                .conflicts_with("hash"))
+            .arg(Arg::with_name("line")
+                .help("Only print the given line of text, or range of lines (e.g. `2-5`). Lines \
+                       are numbered from 1.")
+                .long("line")
+                .takes_value(true)
+                .value_name("N"))
+            .arg(Arg::with_name("json-pretty")
+                .help("Parse the data as JSON and print it indented")
+                .long("json-pretty")
+                .conflicts_with_all(&["types", "jsonl"]))
+            .arg(Arg::with_name("json-compact")
+                .help("Parse the data as JSON and print it without whitespace")
+                .long("json-compact")
+                .conflicts_with_all(&["json-pretty", "types", "jsonl"]))
+            .arg(Arg::with_name("escape")
+                .help("Print text data as a quoted string literal for the given syntax, to embed \
+                       it in a shell command or source code")
+                .long("escape")
+                .takes_value(true)
+                .value_name("SYNTAX")
+                .possible_values(Syntax::NAMES)
+                .conflicts_with_all(&["hash", "count", "types", "jsonl"]))
+            .arg(Arg::with_name("format-template")
+                .help("Print text data wrapped in a template, e.g. `<pre>{content}</pre>`. The \
+                       placeholders {content}, {type} and {size} (in bytes) are replaced by the \
+                       corresponding values; use {{ and }} for literal braces.")
+                .long("format-template")
+                .takes_value(true)
+                .value_name("TMPL")
+                .conflicts_with_all(&["hash", "count", "types", "jsonl"]))
+            .arg(Arg::with_name("crlf")
+                .help("Convert line endings of text data to CRLF")
+                .long("crlf")
+                .conflicts_with("lf"))
+            .arg(Arg::with_name("lf")
+                .help("Convert line endings of text data to LF")
+                .long("lf"))
+            .arg(Arg::with_name("watch-once")
+                .help("Wait until the clipboard contents change, then print the new contents")
+                .long("watch-once")
+                .conflicts_with_all(&["types", "image"]))
+            .arg(Arg::with_name("watch")
+                .help("Print the clipboard contents each time they change, until interrupted or \
+                       until --deadline")
+                .long("watch")
+                .conflicts_with_all(&["watch-once", "types", "image", "hash", "count", "repeat"]))
+            .arg(Arg::with_name("since")
+                .help("With --watch, the digest of the last contents seen, as printed by --hash \
+                       (sha256, sha1 or md5): if the current contents differ, they are printed \
+                       right away. This lets a watcher resume without repeating contents.")
+                .long("since")
+                .takes_value(true)
+                .value_name("HASH")
+                .requires("watch"))
+            .arg(Arg::with_name("on-change-exec")
+                .help("With --watch, run the given shell command each time the clipboard \
+                       changes, with the new contents on its stdin, instead of printing them. \
+                       Binary contents are only passed if --binary allows them.")
+                .long("on-change-exec")
+                .takes_value(true)
+                .value_name("CMD")
+                .requires("watch"))
+            .arg(Arg::with_name("timestamp")
+                .help("With --watch, prefix each change with its RFC 3339 timestamp in brackets. \
+                       Only supported for text data.")
+                .long("timestamp")
+                .requires("watch")
+                .conflicts_with("on-change-exec"))
+            .arg(Arg::with_name("timeout")
+                .help("With --watch-once, give up after the given number of seconds, and exit \
+                       with code 3")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .requires("watch-once"))
+            .arg(Arg::with_name("deadline")
+                .help("With --watch-once or --watch, stop waiting at the given RFC 3339 time (e.g. \
+                       `2021-06-01T18:00:00+02:00`), and exit with code 3 if the clipboard didn't \
+                       change until then")
+                .long("deadline")
+                .takes_value(true)
+                .value_name("TIMESTAMP")
+                .conflicts_with("timeout"))
+            .arg(Arg::with_name("buffer-size")
+                .help("Size in bytes of the output buffer (64 KiB by default)")
+                .long("buffer-size")
+                .takes_value(true)
+                .value_name("BYTES"))
+            .arg(Arg::with_name("content-only")
+                .help("Only print the clipboard data: no trailing newline, hints or warnings, \
+                       whatever the other options. Options that print something other than the \
+                       data, such as --hash or --watch, are rejected.")
+                .long("content-only"))
+            .arg(Arg::with_name("bracketed-paste")
+                .help("When printing text to a terminal, wrap it in bracketed paste sequences, so \
+                       that programs reading the terminal (such as shells) treat it as pasted \
+                       text instead of running each line as it is typed")
+                .long("bracketed-paste")
+                .conflicts_with_all(&[
+                    "types", "jsonl", "watch", "hash", "count", "preview", "repeat",
+                ]))
+            .arg(Arg::with_name("hint")
+                .help("After printing, list the other formats available in the clipboard on \
+                       stderr. This is the default when stdout is a terminal.")
+                .long("hint"))
+            .arg(Arg::with_name("quiet")
+                .help("Don't print hints on stderr")
+                .long("quiet")
+                .short("q")
+                .conflicts_with("hint")))
         .subcommand(SubCommand::with_name("list-types").about("Prints types currently in clipboard")
             .arg(Arg::with_name("system")
                 .help("Display native content types, instead of using cliptool aliases")
                 .long("system")
-                .short("s")))
+                .short("s"))
+            .arg(Arg::with_name("delimiter")
+                .help("Separator printed between types. Recognizes the escapes \\0, \\t, \\n \
+                       and \\f. Defaults to a newline.")
+                .long("delimiter")
+                .short("d")
+                .takes_value(true)
+                .value_name("STR"))
+            .arg(Arg::with_name("null")
+                .help("Separate types with NUL characters, like `--delimiter '\\0'`")
+                .long("null")
+                .short("0")
+                .conflicts_with("delimiter"))
+            .arg(Arg::with_name("filter")
+                .help("Only show types matching the pattern, which may be a glob (e.g. `image/*`) \
+                       or a substring (e.g. `png`). Matching ignores case.")
+                .long("filter")
+                .takes_value(true)
+                .value_name("PATTERN"))
+            .arg(Arg::with_name("require")
+                .help("Instead of listing types, check that all the given comma-separated types \
+                       are in the clipboard, and exit with code 1 if some are missing")
+                .long("require")
+                .takes_value(true)
+                .value_name("TYPES")
+                .use_delimiter(true))
+            .arg(Arg::with_name("no-dedup")
+                .help("List an alias once for each native type it stands for, in the order given \
+                       by the clipboard, instead of sorting and deduplicating aliases")
+                .long("no-dedup")
+                .conflicts_with("system"))
+            .arg(Arg::with_name("count")
+                .help("Print the number of distinct types in the clipboard instead of listing \
+                       them")
+                .long("count")
+                .conflicts_with_all(&["system", "filter", "require", "no-dedup"]))
+            .arg(Arg::with_name("tree")
+                .help("Group types by category (text, image, document, web, custom), printing \
+                       each category name followed by its types, indented")
+                .long("tree")
+                .conflicts_with_all(&["delimiter", "null"]))
+            .arg(Arg::with_name("print0-list")
+                .help("List native types separated by NUL characters, in the order given by the \
+                       clipboard, for scripts. Equivalent to `--system --null`.")
+                .long("print0-list")
+                .conflicts_with_all(&["delimiter", "no-dedup", "count", "tree"])))
         .subcommand(SubCommand::with_name("copy").about("Set data in clipboard")
             .arg(Arg::with_name("type")
                 .help("Format of the data. Must be one of `url`, `html`, \
                        `pdf`, `png`, `rtf`, or `text`. For other formats, use --system-type, \
-                       or prefix your type with an at sign (@).")
+                       or prefix your type with an at sign (@). On Windows and macOS, `files` \
+                       stands for the list of copied files, one path per line.")
                 .long("type")
                 .short("t")
                 .takes_value(true))
@@ -66,207 +532,1435 @@ pub fn main() {
                        dependent; for a portable alternative, use --type.")
                 .long("system-type")
                 .takes_value(true))
+            .arg(Arg::with_name("system-type-ci")
+                .help("Normalize the case of --system-type according to the platform's \
+                       conventions, e.g. `Text/HTML` becomes `text/html`, so that types typed \
+                       with the wrong case are still found")
+                .long("system-type-ci")
+                .requires("system-type"))
             .arg(Arg::with_name("json")
                 .help("Expect a JSON map of data formats to content for each format")
                 .long("json")
                 .short("j"))
+            .arg(Arg::with_name("infer-from")
+                .help("Use the content type matching the extension of the given file name. The \
+                       file is not read.")
+                .long("infer-from")
+                .takes_value(true)
+                .value_name("PATH"))
+            .arg(Arg::with_name("json-file")
+                .help("Like --json, but read the JSON map from the given file instead of stdin")
+                .long("json-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with_all(&["type", "system-type"]))
+            .arg(Arg::with_name("framed")
+                .help("Read several formats from stdin, as records of the form \
+                       `<type>\\n<length in bytes>\\n<data>`, optionally separated by newlines. \
+                       Unlike --json, this supports binary data.")
+                .long("framed"))
+            .arg(Arg::with_name("files")
+                .help("Copy the given files, each as the format matching its extension, instead \
+                       of reading stdin. Files with unknown extensions are skipped.")
+                .long("files")
+                .takes_value(true)
+                .multiple(true)
+                .value_name("PATH")
+                .conflicts_with_all(&["json-file", "exact-length"]))
+            .arg(Arg::with_name("table")
+                .help("Read CSV or TSV data, and copy it both as tab-separated text and as an \
+                       HTML table, as spreadsheets do")
+                .long("table")
+                .conflicts_with_all(&["json-file", "image"]))
             .group(ArgGroup::with_name("format")
-                .args(&["type", "system-type", "json"])))
-        .get_matches();
+                .args(&["type", "system-type", "json", "infer-from", "framed", "files", "table"]))
+            .arg(Arg::with_name("strict-type")
+                .help("Only accept aliases as types, in --type and in JSON and framed input, \
+                       rejecting custom types prefixed with an at sign (@), to catch typos")
+                .long("strict-type")
+                .conflicts_with("system-type"))
+            .arg(Arg::with_name("from-base64-file")
+                .help("Read the data from a file containing its base64 encoding, instead of \
+                       stdin")
+                .long("from-base64-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with_all(&[
+                    "json", "json-file", "framed", "files", "image", "infer-from",
+                ]))
+            .arg(Arg::with_name("value")
+                .help("Copy the given string instead of reading stdin")
+                .long("value")
+                .short("V")
+                .takes_value(true)
+                .value_name("STR")
+                .conflicts_with_all(&["json", "json-file", "framed", "files", "image"]))
+            .arg(Arg::with_name("exec")
+                .help("Run the given shell command and copy its output, instead of reading stdin")
+                .long("exec")
+                .takes_value(true)
+                .value_name("CMD")
+                .conflicts_with_all(&["json", "json-file", "framed", "files", "image"]))
+            .group(ArgGroup::with_name("input")
+                .args(&["value", "exec", "from-base64-file"]))
+            .arg(Arg::with_name("exact-length")
+                .help("Read exactly N bytes from stdin, instead of reading until the end of \
+                       input. Fails if fewer bytes are available.")
+                .long("exact-length")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with_all(&["json-file", "image", "input"]))
+            .arg(Arg::with_name("compress")
+                .help("Compress the data with gzip before copying it. Only supported for custom \
+                       types, since applications won't expect compressed data under the usual \
+                       ones; by convention, their name should end with `+gzip`.")
+                .long("compress")
+                .conflicts_with_all(&["json", "json-file", "framed", "files", "image"]))
+            .arg(Arg::with_name("max-size")
+                .help("Refuse to copy more than the given number of bytes, counting all formats \
+                       together")
+                .long("max-size")
+                .takes_value(true)
+                .value_name("BYTES")
+                .conflicts_with("image"))
+            .arg(Arg::with_name("dry-run")
+                .help("Parse the input and print what would be copied to stderr, without \
+                       modifying the clipboard")
+                .long("dry-run")
+                .short("n"))
+            .arg(Arg::with_name("stats")
+                .help("After copying, print a line of JSON on stderr giving the size in bytes of \
+                       each type copied and their total, such as {\"version\": 1, \"types\": \
+                       [{\"type\": \"text\", \"size\": 5}], \"total\": 5}")
+                .long("stats")
+                .conflicts_with("dry-run"))
+            .arg(Arg::with_name("atomic")
+                .help("After copying, read each type back and fail if it differs from the data \
+                       copied, reporting the first mismatch")
+                .long("atomic")
+                .conflicts_with("dry-run"))
+            .arg(Arg::with_name("trim-indent")
+                .help("Remove the indentation common to all lines of text data, keeping their \
+                       relative indentation, e.g. to copy an indented block from a script. Has no \
+                       effect for other types.")
+                .long("trim-indent"))
+            .arg(Arg::with_name("quiet")
+                .help("Don't warn about single-line data ending with a newline, which is only \
+                       done when stderr is a terminal, or about --delete not being atomic")
+                .long("quiet")
+                .short("q"))
+            .arg(Arg::with_name("sanitize-html")
+                .help("Remove scripts, styles, event handlers and other potentially dangerous \
+                       markup from `html` data, keeping only common formatting elements and \
+                       http(s) links. Has no effect for other types.")
+                .long("sanitize-html"))
+            .arg(Arg::with_name("url-encode")
+                .help("Percent-encode characters that are not valid in URLs, such as spaces, in \
+                       `url` data. Has no effect for other types.")
+                .long("url-encode"))
+            .arg(Arg::with_name("crlf")
+                .help("Convert line endings of text data to CRLF")
+                .long("crlf")
+                .conflicts_with("lf"))
+            .arg(Arg::with_name("lf")
+                .help("Convert line endings of text data to LF")
+                .long("lf"))
+            .arg(Arg::with_name("lowercase")
+                .help("Convert text data to lowercase. Has no effect for other types.")
+                .long("lowercase")
+                .conflicts_with("uppercase"))
+            .arg(Arg::with_name("uppercase")
+                .help("Convert text data to uppercase. Has no effect for other types.")
+                .long("uppercase"))
+            .arg(Arg::with_name("clear-first")
+                .help("Clear the clipboard before setting the new data, so that no formats from \
+                       previous contents remain, even on platforms where setting data doesn't \
+                       replace all formats")
+                .long("clear-first")
+                .conflicts_with("merge"))
+            .arg(Arg::with_name("merge")
+                .help("Keep the formats currently in the clipboard, only replacing the ones being \
+                       copied. This is not atomic, and formats that cannot be read back are lost.")
+                .long("merge"))
+            .arg(Arg::with_name("delete")
+                .help("Remove the type given with --type or --system-type from the clipboard, \
+                       keeping the other formats, instead of setting data. The other formats are \
+                       read and set again, so this is not atomic, and formats that cannot be read \
+                       back are lost.")
+                .long("delete")
+                .conflicts_with_all(&[
+                    "json", "json-file", "infer-from", "framed", "files", "table", "input",
+                    "image", "clear-first", "merge", "dry-run",
+                ]))
+            .arg(Arg::with_name("image")
+                .help("Copy the image in the given file (PNG, JPEG, BMP, GIF...) as an image, \
+                       as GUI applications expect, rather than as raw bytes")
+                .long("image")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with_all(&["type", "system-type", "json", "json-file", "dry-run"]))
+            .arg(Arg::with_name("from-primary")
+                .help("Copy the primary selection to the clipboard, instead of reading stdin. \
+                       The primary selection exists on X11 and Wayland, and only holds text.")
+                .long("from-primary")
+                .conflicts_with_all(&["format", "json-file", "image", "merge", "dry-run"]))
+            .arg(Arg::with_name("from-clipboard")
+                .help("Copy the text of the clipboard to the primary selection, instead of \
+                       reading stdin. The primary selection exists on X11 and Wayland.")
+                .long("from-clipboard")
+                .conflicts_with_all(&[
+                    "from-primary", "format", "json-file", "image", "merge", "dry-run",
+                ])))
+        .subcommand(SubCommand::with_name("swap")
+            .about("Exchanges the contents of the clipboard and the primary selection, on X11 \
+                    and Wayland. Only the text of the clipboard is put in the primary selection."))
+        .subcommand(SubCommand::with_name("info")
+            .about("Prints the size of each type in the clipboard, their total, and the largest")
+            .arg(Arg::with_name("human")
+                .help("Print sizes in KiB, MiB or GiB rather than bytes")
+                .long("human")
+                .short("H")))
+        .subcommand(SubCommand::with_name("edit")
+            .about("Opens the clipboard text in $VISUAL or $EDITOR, and copies the result back"))
+        .subcommand(SubCommand::with_name("probe")
+            .about("Prints the platform, clipboard backend, and the types in the clipboard with \
+                    their sizes as a JSON document, for bug reports"))
+        .subcommand(SubCommand::with_name("selftest")
+            .about("Copies text and a small image, checks that they read back unchanged, and \
+                    restores the clipboard, to diagnose problems with the clipboard backend")
+            .setting(AppSettings::Hidden))
+        .subcommand(SubCommand::with_name("backend")
+            .about("Prints the platform and clipboard backend in use, for bug reports")
+            .setting(AppSettings::Hidden))
+}
+
+pub fn main() {
+    human_panic::setup_panic!();
+
+    let matches = build_app().get_matches();
+    if matches.is_present("mono") {
+        fmt::set_default_color_when(ColorWhen::Never);
+    } else if let Some(when) = matches.value_of("color").and_then(ColorWhen::from_name) {
+        fmt::set_default_color_when(when);
+    }
 
-    let mut clipboard = Clipboard::new().expect("unable to open clipboard");
+    if matches.is_present("version-json") {
+        let version = serde_json::json!({
+            "version": cliptools::JSON_VERSION,
+            "name": "cliptools",
+            "crate_version": VERSION,
+            "target": TARGET,
+            "commit": GIT_COMMIT,
+        });
+        // As with --version, a closed stdout isn't worth reporting.
+        let _ = writeln!(std::io::stdout(), "{}", version);
+        return;
+    }
+
+    if matches.subcommand_name().is_none() {
+        // Print the full usage rather than a terse error, for users running cliptools without
+        // arguments to see what it does.
+        let _ = build_app().write_help(&mut std::io::stderr());
+        eprintln!();
+        std::process::exit(2);
+    }
+
+    let attempts =
+        if matches.is_present("backoff-on-busy") { backoff::DEFAULT_ATTEMPTS } else { 1 };
+    let mut clipboard =
+        Backoff::new(SystemClipboard::new().expect("unable to open clipboard"), attempts);
+
+    let selection = matches.value_of("clipboard").and_then(Selection::from_name);
+    let selection = selection.expect("validated by clap");
 
     let (sc, sc_matches) = matches.subcommand();
-    let ok = match sc {
-        "paste" => paste(&mut clipboard, sc_matches.unwrap()),
-        "list-types" => list(&mut clipboard, sc_matches.unwrap().is_present("system")),
-        "copy" => copy(&mut clipboard, sc_matches.unwrap()),
+    let ok = cliptools::select(&mut clipboard, selection).and_then(|_| Aliases::load());
+    let ok = ok.and_then(|mut aliases| {
+        for spec in matches.values_of("alias").into_iter().flatten() {
+            aliases.add_inline(spec)?;
+        }
+        aliases.set_strict(sc_matches.map_or(false, |m| m.is_present("strict-type")));
+        Ok(aliases)
+    });
+    let ok = ok.and_then(|aliases| match sc {
+        "paste" => paste(&mut clipboard, &aliases, sc_matches.unwrap()),
+        "list-types" => list(&mut clipboard, &aliases, sc_matches.unwrap()),
+        "copy" => copy(&mut clipboard, &aliases, sc_matches.unwrap()),
+        "info" => info(&mut clipboard, &aliases, sc_matches.unwrap()),
+        "edit" => cliptools::edit::edit_text(&mut clipboard).map(|modified| {
+            if !modified {
+                eprintln!("text not modified, leaving clipboard untouched");
+            }
+        }),
+        "swap" => cliptools::swap_selections(&mut clipboard),
+        "selftest" => selftest(&mut clipboard),
+        "probe" => probe(&mut clipboard, &aliases),
+        "backend" => backend(),
         "" => Err(CliptoolsError::ArgumentError("you must specify a subcommand".into()).into()),
         _ => Err(CliptoolsError::ArgumentError(format!("unknown subcommand {}", sc)).into()),
-    };
+    });
 
     if let Err(s) = ok {
+        if cliptools::is_broken_pipe(&s) {
+            std::process::exit(0);
+        }
         let cliptools_error = s.downcast_ref::<CliptoolsError>().expect("unexpected error type");
-        let colorizer = Colorizer::default();
-        print_error(&s, &colorizer);
+        match matches.value_of("error-format") {
+            Some("json") => print_error_json(&s, cliptools_error),
+            _ => print_error(&s, &Colorizer::default()),
+        }
         std::process::exit(cliptools_error.exit_code())
     }
 }
 
-fn paste(board: &mut Clipboard, matches: &ArgMatches) -> Result<()> {
+fn paste<B: ClipboardBackend>(
+    board: &mut B,
+    aliases: &Aliases,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let content_only = matches.is_present("content-only");
+    if content_only {
+        let incompatible = [
+            "types",
+            "jsonl",
+            "watch",
+            "hash",
+            "count",
+            "preview",
+            "escape",
+            "format-template",
+            "bracketed-paste",
+            "list-types",
+            "count-formats",
+            "table",
+        ];
+        if let Some(flag) = incompatible.iter().find(|flag| matches.is_present(flag)) {
+            return Err(CliptoolsError::ArgumentError(format!(
+                "--content-only cannot be used with --{}",
+                flag
+            ))
+            .into());
+        }
+        if let Some(t) = matches.value_of("type").filter(|t| is_list_type(t) || is_files_type(t)) {
+            return Err(CliptoolsError::ArgumentError(format!(
+                "--content-only cannot be used with --type {}",
+                t
+            ))
+            .into());
+        }
+    }
+    if let Some(path) = matches.value_of("image") {
+        return images::paste_image_file(board, Path::new(path));
+    }
+    if matches.is_present("list-types") || matches.value_of("type").map_or(false, is_list_type) {
+        let types = cliptools::list(board, aliases, false, None, true)?;
+        return print_type_list(&types, "\n");
+    }
+    if matches.value_of("type").map_or(false, is_files_type) {
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        for path in files::get_files(board)? {
+            writeln!(out, "{}", path).context(CliptoolsError::InternalError)?;
+        }
+        return Ok(());
+    }
+    if matches.is_present("owner-info") {
+        eprintln!("owner: {}", board.owner().unwrap_or_else(|| "unknown".into()));
+    }
+    if matches.is_present("count-formats") {
+        return print_type_count(board, aliases);
+    }
+    if matches.is_present("table") {
+        let html = cliptools::get(board, &ContentType::Html).ok();
+        let rows = html.and_then(|html| table::parse_html_table(&String::from_utf8_lossy(&html)));
+        let text = match rows {
+            Some(rows) => table::to_tsv(&rows),
+            None => cliptools::get_text(board)?,
+        };
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        show_string(&mut out, &text, !matches.is_present("no-newline"))?;
+        return out.flush().context(CliptoolsError::InternalError);
+    }
+    if matches.is_present("jsonl") {
+        return paste_jsonl(board, aliases);
+    }
+
     let binary_allowed = {
         match matches.value_of("binary") {
             Some("auto") => !is_a_tty(false),
-            None | Some("always") => true,
+            Some("always") => true,
             Some("never") => false,
+            None if matches.is_present("binary") => true,
+            None => env_allows_binary().map_or(true, |allowed| allowed || !is_a_tty(false)),
             other => panic!("unexpected value for binary flag: {:?}", other),
         }
     };
 
     let ct = if let Some(t) = matches.value_of("type") {
-        let converted = string_to_ct(t).ok_or_else(|| {
-            CliptoolsError::ArgumentError(format!(
-                "unknown type: {}; try using --system-type to specify a system native type",
-                t
+        Some(parse_type(t, aliases)?)
+    } else if matches.is_present("interactive") {
+        Some(pick_type(board, aliases)?)
+    } else {
+        matches.value_of("system-type").map(|t| system_type(t, matches))
+    };
+
+    let detect_binary = matches.is_present("force-binary-detection");
+    let warn_binary = matches.is_present("warn-binary") && !content_only;
+    let add_newline = !matches.is_present("no-newline") && !content_only;
+    let fail_if_empty = matches.is_present("fail-if-empty");
+    let require_utf8 = matches.is_present("require-utf8");
+    let hint = (matches.is_present("hint") || (is_a_tty(false) && !matches.is_present("quiet")))
+        && !content_only;
+    let shown = ct.as_ref().map_or_else(|| "text".into(), |ct| show_ct(ct, aliases));
+    for flag in &["charset-detect", "grep", "expand-tabs", "unexpand-tabs"] {
+        if matches.is_present(flag) && !ct.as_ref().map_or(true, is_textual) {
+            return Err(CliptoolsError::ArgumentError(format!(
+                "--{} only supports textual data, not {}",
+                flag, shown
+            ))
+            .into());
+        }
+    }
+    if matches.is_present("as-html") && ct != Some(ContentType::Rtf) {
+        return Err(CliptoolsError::ArgumentError(format!(
+            "--as-html only supports rtf data, not {}",
+            shown
+        ))
+        .into());
+    }
+
+    let deadline = matches.value_of("deadline").map(watch::parse_deadline).transpose()?;
+    if deadline.is_some() && !matches.is_present("watch") && !matches.is_present("watch-once") {
+        return Err(CliptoolsError::ArgumentError(
+            "--deadline requires --watch or --watch-once".into(),
+        )
+        .into());
+    }
+
+    if matches.is_present("watch") {
+        let since = matches.value_of("since");
+        if let Some(since) = since.filter(|since| hash::algorithm_of_digest(since).is_none()) {
+            return Err(CliptoolsError::ArgumentError(format!(
+                "invalid digest: {}; expected a sha256, sha1 or md5 hex digest",
+                since
             ))
-        })?;
-        Some(converted)
+            .into());
+        }
+        let timestamp = matches.is_present("timestamp");
+        let binary_allowed = binary_allowed && !require_utf8;
+        if let Some(command) = matches.value_of("on-change-exec") {
+            return paste_watch_exec(
+                board,
+                ct.as_ref(),
+                deadline,
+                since,
+                command,
+                binary_allowed,
+                detect_binary,
+            );
+        }
+        return paste_watch(
+            board,
+            ct.as_ref(),
+            deadline,
+            since,
+            timestamp,
+            binary_allowed,
+            detect_binary,
+        );
+    }
+
+    let buffer_size =
+        matches.value_of("buffer-size").map_or(Ok(DEFAULT_BUFFER_SIZE), parse_count)?;
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::with_capacity(buffer_size, stdout.lock());
+    if let Some(types) = matches.values_of("types") {
+        let record_separator = matches.value_of("record-separator");
+        let separator = match (record_separator, matches.value_of("separator")) {
+            (Some("nul"), _) => "\0".into(),
+            (Some("newline"), _) => "\n".into(),
+            (Some("custom"), Some(separator)) | (None, Some(separator)) => unescape(separator),
+            _ if binary_allowed => "\0".into(),
+            _ => "\n".into(),
+        };
+        let add_newline = add_newline && separator != "\0";
+        let segments = get_types(board, aliases, types)?;
+        // Separators alone don't count as content.
+        if segments.iter().all(Vec::is_empty) {
+            check_not_empty(b"", fail_if_empty)?;
+        }
+        if require_utf8 {
+            segments.iter().try_for_each(|val| as_text(val).map(|_| ()))?;
+        }
+        for (i, val) in segments.iter().enumerate() {
+            if i > 0 {
+                out.write_all(separator.as_bytes()).context(CliptoolsError::InternalError)?;
+            }
+            let add_newline = add_newline && i == segments.len() - 1;
+            warn_if_binary(val, binary_allowed && warn_binary);
+            show_content(&mut out, val, binary_allowed, detect_binary, add_newline)?;
+        }
     } else {
-        matches.value_of("system-type").map(|t| ContentType::Custom(t.into()))
+        let val = if matches.is_present("watch-once") {
+            let timeout = matches.value_of("timeout").map(parse_timeout).transpose()?;
+            watch::wait_for_change(board, ct.as_ref(), timeout.or(deadline))?
+        } else {
+            match &ct {
+                Some(ContentType::Text) | None if matches.is_present("text-fallback") => {
+                    cliptools::get_text_with_fallback(board)?.into_bytes()
+                },
+                Some(ct) => cliptools::get(board, ct)?,
+                None => cliptools::get_text(board)?.into_bytes(),
+            }
+        };
+        check_not_empty(&val, fail_if_empty)?;
+        let val = if matches.is_present("decompress") { encoding::gunzip(&val)? } else { val };
+        let val = if matches.is_present("charset-detect") {
+            let charset = encoding::detect_charset(&val);
+            eprintln!("charset: {}", charset.name());
+            if matches.is_present("transcode") {
+                encoding::to_utf8(&val, charset).into_bytes()
+            } else {
+                val
+            }
+        } else {
+            val
+        };
+        if matches.is_present("annotate") && !matches.is_present("quiet") {
+            let annotation = serde_json::json!({
+                "version": cliptools::JSON_VERSION,
+                "type": shown,
+                "size": val.len(),
+                "encoding": sniff::detect_encoding(&val),
+                "utf8": std::str::from_utf8(&val).is_ok(),
+            });
+            eprintln!("{}", annotation);
+        }
+        if require_utf8 {
+            as_text(&val)?;
+        }
+        if let Some(t) = matches.value_of("ensure-type") {
+            if !sniff::conforms_to(&parse_type(t, aliases)?, &val) {
+                return Err(CliptoolsError::TypeMismatch(t.into()).into());
+            }
+        }
+        if matches.is_present("ascii-only") {
+            if let Some(i) = val.iter().position(|b| !b.is_ascii()) {
+                return Err(CliptoolsError::NonAsciiError(format!(
+                    "byte 0x{:02x} at offset {}",
+                    val[i], i
+                ))
+                .into());
+            }
+        }
+        if matches.is_present("validate") {
+            let text = ContentType::Text;
+            return match sniff::validate(ct.as_ref().unwrap_or(&text), &val) {
+                Some(result) => result.map_err(|e| CliptoolsError::InvalidContent(e).into()),
+                None => Err(CliptoolsError::ArgumentError(format!(
+                    "cannot validate {} data; supported types are text, png and JSON",
+                    shown
+                ))
+                .into()),
+            };
+        }
+        let val = match matches.value_of("select") {
+            Some(selector) => select(&ct, &val, selector, matches)?,
+            None => val,
+        };
+        let val = match &ct {
+            Some(ContentType::Url) if matches.is_present("normalize-url") => {
+                urls::normalize_uri_list(as_text(&val)?)?.into_bytes()
+            },
+            _ => val,
+        };
+        let val = match &ct {
+            Some(ct @ ContentType::Html) | Some(ct @ ContentType::Rtf)
+                if matches.is_present("as-text") =>
+            {
+                let source = as_text(&val)?;
+                match ct {
+                    ContentType::Html => markup::html_to_text(source),
+                    _ => markup::rtf_to_text(source),
+                }
+                .into_bytes()
+            },
+            Some(ContentType::Rtf) if matches.is_present("as-html") => {
+                markup::rtf_to_html(as_text(&val)?).into_bytes()
+            },
+            Some(ContentType::Url) if matches.is_present("url-decode") => {
+                urls::percent_decode(&val)?
+            },
+            _ => val,
+        };
+        let val = if matches.is_present("json-pretty") || matches.is_present("json-compact") {
+            let pretty = matches.is_present("json-pretty");
+            text::reformat_json(as_text(&val)?, pretty)?.into_bytes()
+        } else {
+            val
+        };
+        let val = match (line_ending(matches), &ct) {
+            (Some(ending), Some(ct)) => convert_textual(ct, val, ending),
+            (Some(ending), None) => convert_textual(&ContentType::Text, val, ending),
+            (None, _) => val,
+        };
+        let val = if let Some(width) = matches.value_of("expand-tabs") {
+            text::expand_tabs(as_text(&val)?, parse_tab_width(width)?).into_bytes()
+        } else if let Some(width) = matches.value_of("unexpand-tabs") {
+            text::unexpand_tabs(as_text(&val)?, parse_tab_width(width)?).into_bytes()
+        } else {
+            val
+        };
+        let val = match matches.value_of("line") {
+            Some(line) => {
+                let range = line.parse::<LineRange>().map_err(CliptoolsError::ArgumentError)?;
+                let lines = text::select_lines(as_text(&val)?, range).ok_or_else(|| {
+                    anyhow::Error::msg(format!("clipboard text has fewer than {} lines", range.end))
+                        .context(CliptoolsError::DataNotFound)
+                })?;
+                lines.as_bytes().to_vec()
+            },
+            None => val,
+        };
+        let val = match matches.value_of("grep") {
+            Some(pattern) => {
+                let pattern = text::parse_pattern(pattern, matches.is_present("ignore-case"))?;
+                let text = as_text(&val)?;
+                if cliptools::looks_binary(text) {
+                    return Err(CliptoolsError::BinaryContent.into());
+                }
+                let lines = text::grep_lines(text, &pattern, matches.is_present("invert"));
+                if lines.is_empty() {
+                    return Err(
+                        anyhow::Error::msg("no lines match").context(CliptoolsError::DataNotFound)
+                    );
+                }
+                lines.into_bytes()
+            },
+            None => val,
+        };
+        let val = if let Some(n) = matches.value_of("head") {
+            text::head_lines(as_text(&val)?, parse_count(n)?).as_bytes().to_vec()
+        } else if let Some(n) = matches.value_of("tail") {
+            text::tail_lines(as_text(&val)?, parse_count(n)?).as_bytes().to_vec()
+        } else {
+            val
+        };
+        let val = match matches.value_of("escape").and_then(Syntax::from_name) {
+            Some(syntax) => escape::escape(as_text(&val)?, syntax).into_bytes(),
+            None => val,
+        };
+        let val = match matches.value_of("format-template") {
+            Some(template) => {
+                let size = val.len().to_string();
+                let content = as_text(&val)?;
+                let values = [("content", content), ("type", &shown[..]), ("size", &size[..])];
+                text::fill_template(template, &values)
+                    .map_err(CliptoolsError::ArgumentError)?
+                    .into_bytes()
+            },
+            None => val,
+        };
+        if let Some(algorithm) = matches.value_of("hash").and_then(HashAlgorithm::from_name) {
+            writeln!(out, "{}", hash::hex_digest(algorithm, &val))
+                .context(CliptoolsError::InternalError)?;
+        } else if let Some(unit) = matches.value_of("count") {
+            let count = match unit {
+                "bytes" => val.len(),
+                "chars" => as_text(&val)?.chars().count(),
+                "lines" => as_text(&val)?.lines().count(),
+                "words" => as_text(&val)?.split_whitespace().count(),
+                other => panic!("unexpected value for count flag: {:?}", other),
+            };
+            writeln!(out, "{}", count).context(CliptoolsError::InternalError)?;
+        } else if let Some(n) = matches.value_of("preview") {
+            let preview = &val[..parse_count(n)?.min(val.len())];
+            show_string(&mut out, &escape::caret_notation(preview), add_newline)?;
+        } else {
+            let repeat = matches.value_of("repeat").map_or(Ok(1), parse_count)?;
+            let separator = matches.value_of("separator").map_or_else(String::new, unescape);
+            // Also bound the repetitions themselves, so that empty data can't loop for ages.
+            if repeat > MAX_REPEAT_OUTPUT {
+                return Err(CliptoolsError::ArgumentError(format!(
+                    "cannot repeat the data more than {} times",
+                    MAX_REPEAT_OUTPUT
+                ))
+                .into());
+            }
+            if (val.len() + separator.len()).saturating_mul(repeat) > MAX_REPEAT_OUTPUT {
+                return Err(CliptoolsError::ArgumentError(format!(
+                    "repeating the data {} times would print more than {} bytes",
+                    repeat, MAX_REPEAT_OUTPUT
+                ))
+                .into());
+            }
+            warn_if_binary(&val, binary_allowed && warn_binary);
+            let bracketed = matches.is_present("bracketed-paste") && is_a_tty(false);
+            let (val, add_newline) = match std::str::from_utf8(&val) {
+                // A newline after the end of the paste would be taken as a key press.
+                Ok(text) if bracketed => (escape::bracket_paste(text).into_bytes(), false),
+                _ => (val, add_newline),
+            };
+            for i in 0..repeat {
+                if i > 0 {
+                    out.write_all(separator.as_bytes()).context(CliptoolsError::InternalError)?;
+                }
+                let add_newline = add_newline && i == repeat - 1;
+                show_content(&mut out, &val, binary_allowed, detect_binary, add_newline)?;
+            }
+        }
+    }
+    out.flush().context(CliptoolsError::InternalError)?;
+
+    if hint && matches.values_of("types").is_none() {
+        print_formats_hint(board, aliases, &shown);
+    }
+    Ok(())
+}
+
+/// Prints the formats available in the clipboard besides `shown` on stderr. This is best-effort,
+/// so errors listing types are ignored.
+fn print_formats_hint<B: ClipboardBackend>(board: &mut B, aliases: &Aliases, shown: &str) {
+    let others = match cliptools::list(board, aliases, false, None, true) {
+        Ok(types) => types.into_iter().filter(|t| t != shown).collect::<Vec<_>>(),
+        Err(_) => return,
     };
+    match others.len() {
+        0 => {},
+        1 => eprintln!("1 other format available: {}", others[0]),
+        n => eprintln!("{} other formats available: {}", n, others.join(", ")),
+    }
+}
 
-    let add_newline = !matches.is_present("no-newline");
+/// Prints the clipboard contents each time they change, optionally prefixed with a timestamp.
+fn paste_watch<B: ClipboardBackend>(
+    board: &mut B,
+    ct: Option<&ContentType>,
+    timeout: Option<Duration>,
+    since: Option<&str>,
+    timestamp: bool,
+    binary_allowed: bool,
+    detect_binary: bool,
+) -> Result<()> {
+    if timestamp && !ct.map_or(true, is_textual) {
+        return Err(CliptoolsError::ArgumentError(
+            "--timestamp is only supported for text types".into(),
+        )
+        .into());
+    }
+    watch::watch(board, ct, timeout, since, |val| {
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        if timestamp {
+            let text = as_text(val)?;
+            write!(out, "[{}] ", Local::now().to_rfc3339())
+                .context(CliptoolsError::InternalError)?;
+            cliptools::show_string(&mut out, text, true)?;
+        } else {
+            show_content(&mut out, val, binary_allowed, detect_binary, true)?;
+        }
+        out.flush().context(CliptoolsError::InternalError)
+    })
+}
+
+/// Runs `command` each time the clipboard contents change, with the new contents on its stdin.
+/// Failures of the command are reported, but don't stop watching.
+fn paste_watch_exec<B: ClipboardBackend>(
+    board: &mut B,
+    ct: Option<&ContentType>,
+    timeout: Option<Duration>,
+    since: Option<&str>,
+    command: &str,
+    binary_allowed: bool,
+    detect_binary: bool,
+) -> Result<()> {
+    watch::watch(board, ct, timeout, since, |val| {
+        let is_text = match std::str::from_utf8(val) {
+            Ok(s) => !(detect_binary && cliptools::looks_binary(s)),
+            Err(_) => false,
+        };
+        let result = if is_text || binary_allowed {
+            process::run_with_input(command, val)
+        } else {
+            Err(CliptoolsError::BinaryContent.into())
+        };
+        if let Err(err) = result {
+            print_error(&err, &Colorizer::default());
+        }
+        Ok(())
+    })
+}
+
+/// Implements `paste --interactive`: lists the types in the clipboard on stderr, and asks the user
+/// to pick one on the terminal.
+fn pick_type<B: ClipboardBackend>(board: &mut B, aliases: &Aliases) -> Result<ContentType> {
+    let tty = open_tty().map_err(|e| {
+        anyhow::Error::msg(e.to_string())
+            .context(CliptoolsError::ArgumentError("--interactive requires a terminal".into()))
+    })?;
+    let types = cliptools::list(board, aliases, false, None, true)?;
+    if types.is_empty() {
+        return Err(
+            anyhow::Error::msg("the clipboard is empty").context(CliptoolsError::DataNotFound)
+        );
+    }
+    for (i, typ) in types.iter().enumerate() {
+        eprintln!("{:>3}. {}", i + 1, typ);
+    }
+    let mut lines = BufReader::new(tty).lines();
+    loop {
+        eprint!("format to paste [1-{}]: ", types.len());
+        let line = match lines.next() {
+            Some(line) => line.context(CliptoolsError::InternalError)?,
+            None => return Err(CliptoolsError::ArgumentError("no format selected".into()).into()),
+        };
+        match line.trim().parse::<usize>() {
+            Ok(n) if (1..=types.len()).contains(&n) => {
+                return string_to_ct(&types[n - 1], aliases).ok_or_else(|| {
+                    anyhow::Error::msg(format!("unknown type {}", types[n - 1]))
+                        .context(CliptoolsError::InternalError)
+                });
+            },
+            _ => eprintln!("expected a number between 1 and {}", types.len()),
+        }
+    }
+}
+
+/// Opens the terminal for reading, even if stdin is redirected. Fails if stderr, where prompts are
+/// written, isn't a terminal either.
+fn open_tty() -> std::io::Result<File> {
+    if !is_a_tty(true) {
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, "stderr is not a terminal"));
+    }
+    File::open(if cfg!(windows) { "CONIN$" } else { "/dev/tty" })
+}
+
+/// Prints every format in the clipboard as JSON Lines. Formats that can't be read are skipped.
+fn paste_jsonl<B: ClipboardBackend>(board: &mut B, aliases: &Aliases) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    for typ in cliptools::list(board, aliases, false, None, true)? {
+        let ct = string_to_ct(&typ, aliases).ok_or(CliptoolsError::InternalError)?;
+        if let Ok(val) = cliptools::get(board, &ct) {
+            cliptools::show_json_line(&mut out, &typ, &val)?;
+        }
+    }
+    out.flush().context(CliptoolsError::InternalError)
+}
 
-    if let Some(ct) = ct {
-        let val = board
-            .get_content_for_type(&ct)
-            .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::DataNotFound))?;
-        show_content(&val, binary_allowed, add_newline)?;
+/// Converts the value of --system-type to a content type, normalizing its case if requested.
+fn system_type(t: &str, matches: &ArgMatches) -> ContentType {
+    if matches.is_present("system-type-ci") {
+        ContentType::Custom(normalize_system_type(t))
     } else {
-        let val = board
-            .get_text()
-            .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::DataNotFound))?;
-        show_string(&val, add_newline);
+        ContentType::Custom(t.into())
     }
-    std::io::stdout().flush().map_err(anyhow::Error::from)
 }
 
-fn list(board: &mut Clipboard, system: bool) -> Result<()> {
-    let types = board
-        .get_content_types()
-        .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::DataNotFound))?;
-    if system {
+/// Whether a type given on the command line is the `files` pseudo-type, for lists of copied files.
+fn is_files_type(t: &str) -> bool {
+    t.eq_ignore_ascii_case(files::FILES_TYPE)
+}
+
+/// Whether a type given to `paste` is the `list` pseudo-type, which lists the types available.
+fn is_list_type(t: &str) -> bool {
+    t.eq_ignore_ascii_case("list")
+}
+
+/// Converts a type given on the command line to a content type. If `aliases` are strict, custom
+/// types (`@...`) are rejected, so that only aliases are accepted.
+fn parse_type(t: &str, aliases: &Aliases) -> Result<ContentType> {
+    if aliases.is_strict() && t.starts_with('@') {
+        return Err(CliptoolsError::ArgumentError(format!(
+            "custom type {} is not allowed with --strict-type",
+            t
+        ))
+        .into());
+    }
+    string_to_ct(t, aliases).ok_or_else(|| {
+        CliptoolsError::ArgumentError(format!(
+            "unknown type: {}; try using --system-type to specify a system native type",
+            t
+        ))
+        .into()
+    })
+}
+
+/// Fetches each of `types` that is present in the clipboard, warning about the others.
+fn get_types<'a, B: ClipboardBackend>(
+    board: &mut B,
+    aliases: &Aliases,
+    types: impl Iterator<Item = &'a str>,
+) -> Result<Vec<Vec<u8>>> {
+    let colorizer = Colorizer::default();
+    let mut segments = Vec::new();
+    for t in types {
+        let ct = parse_type(t, aliases)?;
+        match cliptools::get(board, &ct) {
+            Ok(val) => segments.push(val),
+            Err(_) => print_warning(&format!("no data for type {}, skipping", t), &colorizer),
+        }
+    }
+    if segments.is_empty() {
+        return Err(anyhow::Error::msg("none of the requested types are in the clipboard")
+            .context(CliptoolsError::DataNotFound));
+    }
+    Ok(segments)
+}
+
+/// Prints a warning on stderr if `warn` is set and `val` is not valid UTF-8.
+fn warn_if_binary(val: &[u8], warn: bool) {
+    if warn && std::str::from_utf8(val).is_err() {
+        print_warning("clipboard data is not valid UTF-8", &Colorizer::default());
+    }
+}
+
+/// Replaces the escapes `\0`, `\t`, `\n`, `\f` and `\\` in separators given on the command line.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => out.push('\0'),
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('f') => out.push('\x0c'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Implements `copy --files`: reads each file as the content type matching its extension.
+fn read_files<'a>(
+    paths: impl Iterator<Item = &'a str>,
+    aliases: &Aliases,
+) -> Result<IndexMap<ContentType, Vec<u8>>> {
+    let mut files: IndexMap<ContentType, (&str, Vec<u8>)> = IndexMap::new();
+    for path in paths {
+        let ext = Path::new(path).extension().and_then(|ext| ext.to_str());
+        let ct = match ext.and_then(extension_to_ct) {
+            Some(ct) => ct,
+            None => {
+                let msg = format!("unknown extension for {}, skipping", path);
+                print_warning(&msg, &Colorizer::default());
+                continue;
+            },
+        };
+        if let Some((other, _)) = files.get(&ct) {
+            return Err(CliptoolsError::ArgumentError(format!(
+                "{} and {} both contain {} data",
+                other,
+                path,
+                show_ct(&ct, aliases)
+            ))
+            .into());
+        }
+        let data = std::fs::read(path)
+            .with_context(|| CliptoolsError::FileError(format!("cannot read {}", path)))?;
+        files.insert(ct, (path, data));
+    }
+    if files.is_empty() {
+        return Err(CliptoolsError::ArgumentError(
+            "none of the given files has a known extension".into(),
+        )
+        .into());
+    }
+    Ok(files.into_iter().map(|(ct, (_, data))| (ct, data)).collect())
+}
+
+/// Whether `data` is a single line followed by a newline, which was probably not meant to be
+/// copied, as added by `echo`.
+fn has_trailing_newline(data: &[u8]) -> bool {
+    match data.strip_suffix(b"\n") {
+        Some(line) => !line.contains(&b'\n'),
+        None => false,
+    }
+}
+
+/// Infers a content type from a file name, falling back to text.
+fn infer_ct(path: &Path) -> ContentType {
+    let ct = path.extension().and_then(|ext| ext.to_str()).and_then(extension_to_ct);
+    ct.unwrap_or_else(|| {
+        let msg = format!("unknown extension for {}, copying as text", path.display());
+        print_warning(&msg, &Colorizer::default());
+        ContentType::Text
+    })
+}
+
+/// Implements `paste --select`, returning the selected data as lines.
+fn select(
+    ct: &Option<ContentType>,
+    val: &[u8],
+    selector: &str,
+    matches: &ArgMatches,
+) -> Result<Vec<u8>> {
+    if !matches!(ct, Some(ContentType::Html)) {
+        return Err(CliptoolsError::ArgumentError("--select requires `--type html`".into()).into());
+    }
+    let selection = if matches.is_present("outer") {
+        HtmlSelection::Outer
+    } else if let Some(name) = matches.value_of("attr") {
+        HtmlSelection::Attr(name)
+    } else {
+        HtmlSelection::Text
+    };
+    let selected = markup::select_html(as_text(val)?, selector, selection)?;
+    if selected.is_empty() {
+        return Err(anyhow::Error::msg(format!("no elements match {}", selector))
+            .context(CliptoolsError::DataNotFound));
+    }
+    Ok(selected.join("\n").into_bytes())
+}
+
+/// Returns the line ending requested with --crlf or --lf, if any.
+fn line_ending(matches: &ArgMatches) -> Option<LineEnding> {
+    if matches.is_present("crlf") {
+        Some(LineEnding::Crlf)
+    } else if matches.is_present("lf") {
+        Some(LineEnding::Lf)
+    } else {
+        None
+    }
+}
+
+/// Converts the line endings of textual data, leaving binary data untouched.
+fn convert_textual(ct: &ContentType, val: Vec<u8>, ending: LineEnding) -> Vec<u8> {
+    if !is_textual(ct) {
+        return val;
+    }
+    match String::from_utf8(val) {
+        Ok(s) => text::convert_line_endings(&s, ending).into_bytes(),
+        Err(e) => e.into_bytes(),
+    }
+}
+
+/// Returns whether binary output was enabled or disabled with the CLIPTOOLS_ALLOW_BINARY
+/// environment variable, or `None` if it isn't set to a boolean.
+fn env_allows_binary() -> Option<bool> {
+    let value = std::env::var("CLIPTOOLS_ALLOW_BINARY").ok()?;
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a numeric command line argument.
+fn parse_count(n: &str) -> Result<usize> {
+    n.parse().map_err(|_| CliptoolsError::ArgumentError(format!("invalid number: {}", n)).into())
+}
+
+fn parse_tab_width(n: &str) -> Result<usize> {
+    match parse_count(n)? {
+        0 => Err(CliptoolsError::ArgumentError("tab width must be at least 1".into()).into()),
+        width => Ok(width),
+    }
+}
+
+/// Parses a duration in seconds, possibly fractional.
+fn parse_timeout(s: &str) -> Result<Duration> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| CliptoolsError::ArgumentError(format!("invalid timeout: {}", s)).into())
+}
+
+/// Interprets clipboard contents as text, for options that only make sense for text.
+fn as_text(val: &[u8]) -> Result<&str> {
+    std::str::from_utf8(val).context(CliptoolsError::Utf8Error)
+}
+
+fn check_not_empty(val: &[u8], fail_if_empty: bool) -> Result<()> {
+    if fail_if_empty && val.is_empty() {
+        Err(anyhow::Error::msg("clipboard content is empty").context(CliptoolsError::DataNotFound))
+    } else {
+        Ok(())
+    }
+}
+
+fn list<B: ClipboardBackend>(board: &mut B, aliases: &Aliases, matches: &ArgMatches) -> Result<()> {
+    if let Some(required) = matches.values_of("require") {
+        let required = required.map(|t| parse_type(t, aliases)).collect::<Result<Vec<_>>>()?;
+        let missing = cliptools::missing_types(board, &required)?;
+        if !missing.is_empty() {
+            let missing = missing.iter().map(|ct| show_ct(ct, aliases)).collect::<Vec<_>>();
+            return Err(anyhow::Error::msg(format!("missing types: {}", missing.join(", ")))
+                .context(CliptoolsError::DataNotFound));
+        }
+        return Ok(());
+    }
+    if matches.is_present("count") {
+        return print_type_count(board, aliases);
+    }
+    let system = matches.is_present("system") || matches.is_present("print0-list");
+    let types = cliptools::list(
+        board,
+        aliases,
+        system,
+        matches.value_of("filter"),
+        !matches.is_present("no-dedup"),
+    )?;
+    if matches.is_present("tree") {
+        let mut tree = BTreeMap::new();
         for typ in types {
-            println!("{}", typ);
+            let ct = if system {
+                Some(ContentType::Custom(typ.clone()))
+            } else {
+                string_to_ct(&typ, aliases)
+            };
+            let category = ct.as_ref().map_or(Category::Custom, cliptools::category);
+            tree.entry(category).or_insert_with(Vec::new).push(typ);
         }
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        for (category, types) in tree {
+            writeln!(out, "{}:", category.name()).context(CliptoolsError::InternalError)?;
+            for typ in types {
+                writeln!(out, "  {}", typ).context(CliptoolsError::InternalError)?;
+            }
+        }
+        return out.flush().context(CliptoolsError::InternalError);
+    }
+    let delimiter = if matches.is_present("null") || matches.is_present("print0-list") {
+        "\0".into()
     } else {
-        let mut converted = types
-            .into_iter()
-            .map(|s| board.normalize_content_type(s))
-            .map(|ct| show_ct(&ct))
-            .collect::<Vec<_>>();
-        converted.sort();
-        converted.dedup();
-        for typ in converted {
-            println!("{}", typ);
+        matches.value_of("delimiter").map_or_else(|| "\n".into(), unescape)
+    };
+    print_type_list(&types, &delimiter)
+}
+
+/// Prints a list of types separated by `delimiter`. Implements `list-types` and
+/// `paste --type list`.
+fn print_type_list(types: &[String], delimiter: &str) -> Result<()> {
+    if !types.is_empty() {
+        // NUL-separated lists are NUL-terminated, like `find -print0`; other lists end with a
+        // newline.
+        let terminator = if delimiter == "\0" { "\0" } else { "\n" };
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        write!(out, "{}{}", types.join(delimiter), terminator)
+            .context(CliptoolsError::InternalError)?;
+    }
+    std::io::stdout().flush().context(CliptoolsError::InternalError)
+}
+
+fn info<B: ClipboardBackend>(board: &mut B, aliases: &Aliases, matches: &ArgMatches) -> Result<()> {
+    let human = matches.is_present("human");
+    let mut sizes = cliptools::get_all(board)?
+        .iter()
+        .map(|(ct, data)| (show_ct(ct, aliases), data.len()))
+        .collect::<Vec<_>>();
+    sizes.sort();
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    for (name, size) in &sizes {
+        writeln!(out, "{}: {}", name, format_size(*size, human))
+            .context(CliptoolsError::InternalError)?;
+    }
+    let sizes = sizes.iter().map(|(name, size)| (name.as_str(), *size)).collect::<Vec<_>>();
+    let totals = cliptools::size_totals(&sizes);
+    writeln!(out, "total: {} in {} types", format_size(totals.total, human), sizes.len())
+        .context(CliptoolsError::InternalError)?;
+    if let Some((name, size)) = totals.largest {
+        writeln!(out, "largest: {} ({})", name, format_size(size, human))
+            .context(CliptoolsError::InternalError)?;
+    }
+    out.flush().context(CliptoolsError::InternalError)
+}
+
+fn selftest<B: ClipboardBackend>(board: &mut B) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let mut failed = 0;
+    for (name, outcome) in selftest::run(board)? {
+        match outcome {
+            Outcome::Passed => writeln!(out, "{}: ok", name),
+            Outcome::Unsupported => writeln!(out, "{}: not supported by the backend", name),
+            Outcome::Failed(reason) => {
+                failed += 1;
+                writeln!(out, "{}: FAILED ({})", name, reason)
+            },
         }
+        .context(CliptoolsError::InternalError)?;
+    }
+    out.flush().context(CliptoolsError::InternalError)?;
+    if failed > 0 {
+        return Err(anyhow::Error::msg(format!("{} format(s) did not round-trip", failed))
+            .context(CliptoolsError::InternalError));
     }
     Ok(())
 }
 
-fn copy(board: &mut Clipboard, matches: &ArgMatches) -> Result<()> {
-    let map: HashMap<ContentType, Vec<u8>> = if matches.is_present("json") {
-        let json: serde_json::Value = serde_json::from_reader(std::io::stdin())
-            .context(CliptoolsError::JsonError("cannot read JSON input".into()))?;
-        let map = json.as_object().ok_or_else(|| {
-            CliptoolsError::JsonError("expected a JSON object at top level".into())
-        })?;
-        map.iter()
-            .map(|(typ, content)| -> Result<(ContentType, Vec<u8>)> {
-                let ct = string_to_ct(typ).ok_or_else(|| {
-                    CliptoolsError::ArgumentError(format!("unknown type: {}", typ))
-                })?;
-                let val = content.as_str().ok_or_else(|| {
-                    CliptoolsError::JsonError(format!("expected a string under key {}", typ))
-                })?;
-                Ok((ct, val.bytes().collect()))
-            })
-            .collect::<Result<HashMap<_, _>>>()?
+fn probe<B: ClipboardBackend>(board: &mut B, aliases: &Aliases) -> Result<()> {
+    let report = cliptools::probe::probe(board, aliases);
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let report = serde_json::to_string_pretty(&report).expect("JSON values serialize");
+    writeln!(out, "{}", report).context(CliptoolsError::InternalError)?;
+    out.flush().context(CliptoolsError::InternalError)
+}
+
+fn backend() -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    writeln!(out, "platform: {}", std::env::consts::OS).context(CliptoolsError::InternalError)?;
+    writeln!(out, "backend: {}", cliptools::backend_name())
+        .context(CliptoolsError::InternalError)?;
+    out.flush().context(CliptoolsError::InternalError)
+}
+
+/// Implements `list-types --count` and `paste --count-formats`.
+fn print_type_count<B: ClipboardBackend>(board: &mut B, aliases: &Aliases) -> Result<()> {
+    let count = cliptools::count_types(board, aliases)?;
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    writeln!(out, "{}", count).context(CliptoolsError::InternalError)?;
+    out.flush().context(CliptoolsError::InternalError)
+}
+
+/// Reads the data to copy from --from-base64-file, --value or --exec if given, or from stdin
+/// otherwise.
+fn read_data(matches: &ArgMatches) -> Result<Vec<u8>> {
+    if let Some(path) = matches.value_of("from-base64-file") {
+        let text = std::fs::read(path)
+            .with_context(|| CliptoolsError::FileError(format!("cannot read {}", path)))?;
+        encoding::decode_base64(&text).map_err(|e| match e.downcast_ref() {
+            Some(CliptoolsError::Base64Error(reason)) => {
+                CliptoolsError::Base64Error(format!("{}: {}", path, reason)).into()
+            },
+            _ => e,
+        })
+    } else if let Some(value) = matches.value_of("value") {
+        Ok(value.as_bytes().to_vec())
+    } else if let Some(command) = matches.value_of("exec") {
+        process::run_capturing(command)
     } else {
-        let ct = if let Some(t) = matches.value_of("type") {
-            string_to_ct(t).ok_or_else(|| {
-                CliptoolsError::ArgumentError(format!(
-                    "unknown type: {}; try using --system-type to specify a system native type",
-                    t
+        read_input(matches)
+    }
+}
+
+/// Reads the data to copy from stdin: either all of it, or exactly the number of bytes given by
+/// --exact-length.
+fn read_input(matches: &ArgMatches) -> Result<Vec<u8>> {
+    let stdin = std::io::stdin();
+    let mut data = Vec::new();
+    match matches.value_of("exact-length") {
+        Some(n) => {
+            let n = parse_count(n)?;
+            stdin
+                .lock()
+                .take(n as u64)
+                .read_to_end(&mut data)
+                .context(CliptoolsError::InternalError)?;
+            if data.len() < n {
+                return Err(anyhow::Error::msg(format!(
+                    "expected {} bytes on stdin, got {}",
+                    n,
+                    data.len()
                 ))
-            })?
+                .context(CliptoolsError::InternalError));
+            }
+        },
+        None => {
+            stdin.lock().read_to_end(&mut data).context(CliptoolsError::InternalError)?;
+        },
+    }
+    Ok(data)
+}
+
+fn copy<B: ClipboardBackend>(board: &mut B, aliases: &Aliases, matches: &ArgMatches) -> Result<()> {
+    if let Some(path) = matches.value_of("image") {
+        return images::copy_image_file(board, Path::new(path));
+    }
+    if matches.is_present("delete") {
+        let ct = if let Some(t) = matches.value_of("type") {
+            parse_type(t, aliases)?
         } else if let Some(t) = matches.value_of("system-type") {
-            ContentType::Custom(t.into())
+            system_type(t, matches)
         } else {
-            ContentType::Text
+            return Err(CliptoolsError::ArgumentError(
+                "--delete requires --type or --system-type".into(),
+            )
+            .into());
         };
-        let mut data = Vec::new();
-        std::io::stdin().read_to_end(&mut data).context(CliptoolsError::InternalError)?;
-        IntoIter::new([(ct, data)]).collect()
-    };
+        if !matches.is_present("quiet") {
+            print_warning(
+                "the other formats are read and set again, so changes made meanwhile by other \
+                 programs may be lost",
+                &Colorizer::default(),
+            );
+        }
+        return cliptools::remove_type(board, &ct);
+    }
+    if matches.is_present("from-primary") {
+        return cliptools::copy_selection(board, Selection::Primary, Selection::Clipboard);
+    } else if matches.is_present("from-clipboard") {
+        return cliptools::copy_selection(board, Selection::Clipboard, Selection::Primary);
+    }
 
-    board
-        .set_content_types(map)
-        .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::InternalError))
-}
-
-fn string_to_ct(s: &str) -> Option<ContentType> {
-    Some(match s.to_ascii_lowercase().as_str() {
-        "url" => ContentType::Url,
-        "html" => ContentType::Html,
-        "pdf" => ContentType::Pdf,
-        "png" => ContentType::Png,
-        "rtf" => ContentType::Rtf,
-        "text" => ContentType::Text,
-        _ => {
-            if s.starts_with('@') {
-                ContentType::Custom(s.chars().skip(1).collect())
+    let warn = |msg: &str| print_warning(msg, &Colorizer::default());
+    let mut map = if let Some(path) = matches.value_of("json-file") {
+        let file = File::open(path)
+            .with_context(|| CliptoolsError::JsonError(format!("cannot open {}", path)))?;
+        cliptools::parse_json_input(BufReader::new(file), aliases, warn)?
+    } else if matches.is_present("json") {
+        cliptools::parse_json_input(&read_input(matches)?[..], aliases, warn)?
+    } else if matches.is_present("framed") {
+        framed::parse_framed(&read_input(matches)?, aliases)?
+    } else if let Some(paths) = matches.values_of("files") {
+        read_files(paths, aliases)?
+    } else if matches.is_present("table") {
+        let input = read_data(matches)?;
+        let text = as_text(&input)?;
+        let rows = table::parse_delimited(text, table::guess_delimiter(text));
+        IntoIter::new([
+            (ContentType::Text, table::to_tsv(&rows).into_bytes()),
+            (ContentType::Html, table::to_html_table(&rows).into_bytes()),
+        ])
+        .collect()
+    } else {
+        let data = read_data(matches)?;
+        if matches.value_of("type").map_or(false, is_files_type) {
+            let paths = as_text(&data)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>();
+            IntoIter::new([files::files_content(&paths)?]).collect()
+        } else {
+            let ct = if let Some(t) = matches.value_of("type") {
+                parse_type(t, aliases)?
+            } else if let Some(t) = matches.value_of("system-type") {
+                system_type(t, matches)
+            } else if let Some(path) = matches.value_of("infer-from") {
+                infer_ct(Path::new(path))
             } else {
-                return None;
+                ContentType::Text
+            };
+            let quiet = matches.is_present("quiet");
+            if has_trailing_newline(&data) && is_textual(&ct) && is_a_tty(true) && !quiet {
+                print_warning(
+                    "the data ends with a newline, which will be pasted too",
+                    &Colorizer::default(),
+                );
             }
-        },
-    })
-}
+            IntoIter::new([(ct, data)]).collect()
+        }
+    };
 
-fn show_content(val: &[u8], binary_allowed: bool, add_newline: bool) -> Result<()> {
-    let utf8 = std::str::from_utf8(val).context(CliptoolsError::Utf8Error);
-    match (utf8, binary_allowed) {
-        (Ok(s), _) => show_string(s, add_newline),
-        (Err(e), false) => return Err(e),
-        _ => std::io::stdout().write_all(val).expect("unable to flush stdout"),
+    if let Some(ending) = line_ending(matches) {
+        map = map
+            .into_iter()
+            .map(|(ct, val)| {
+                let val = convert_textual(&ct, val, ending);
+                (ct, val)
+            })
+            .collect();
     }
-    Ok(())
-}
 
-fn show_string(s: &str, add_newline: bool) {
-    print!("{}", s);
-    if s.as_bytes().last() != Some(&b'\n') && add_newline {
-        println!()
+    let case = if matches.is_present("lowercase") {
+        Some(Case::Lower)
+    } else if matches.is_present("uppercase") {
+        Some(Case::Upper)
+    } else {
+        None
+    };
+    if let (Some(case), Some(text)) = (case, map.get_mut(&ContentType::Text)) {
+        *text = text::change_case(as_text(text)?, case).into_bytes();
     }
-}
 
-fn show_ct(ct: &ContentType) -> String {
-    match ct {
-        ContentType::Text => "text".into(),
-        ContentType::Html => "html".into(),
-        ContentType::Pdf => "pdf".into(),
-        ContentType::Png => "png".into(),
-        ContentType::Rtf => "rtf".into(),
-        ContentType::Url => "url".into(),
-        ContentType::Custom(s) => format!("@{}", s),
+    if matches.is_present("trim-indent") {
+        if let Some(text) = map.get_mut(&ContentType::Text) {
+            *text = text::trim_indent(as_text(text)?).into_bytes();
+        }
     }
-}
 
-#[derive(Error, Debug)]
-pub enum CliptoolsError {
-    #[error("data not found")]
-    DataNotFound,
-    #[error("usage: {0}")]
-    ArgumentError(String),
-    #[error("data in clipboard is not valid UTF-8; try using `--binary always`")]
-    Utf8Error,
-    #[error("invalid JSON input: {0}")]
-    JsonError(String),
-    #[error("internal error")]
-    InternalError,
-}
+    if matches.is_present("sanitize-html") {
+        match map.get_mut(&ContentType::Html) {
+            Some(html) => *html = markup::sanitize_html(as_text(html)?).into_bytes(),
+            None => print_warning(
+                "--sanitize-html has no effect, as no html data is being copied",
+                &Colorizer::default(),
+            ),
+        }
+    }
+
+    if matches.is_present("url-encode") {
+        if let Some(url) = map.get_mut(&ContentType::Url) {
+            *url = urls::percent_encode(url);
+        }
+    }
+
+    if matches.is_present("compress") {
+        if let Some(ct) = map.keys().find(|ct| !matches!(ct, ContentType::Custom(_))) {
+            return Err(CliptoolsError::ArgumentError(format!(
+                "cannot compress {} data; use a custom type, such as @application/x-data+gzip",
+                show_ct(ct, aliases)
+            ))
+            .into());
+        }
+        for val in map.values_mut() {
+            *val = encoding::gzip(val)?;
+        }
+    }
 
-impl CliptoolsError {
-    /// Converts an error into the exit code.
-    ///  - 1 for missing data or clipboard errors
-    ///  - 2 for user errors
-    pub fn exit_code(&self) -> i32 {
-        match self {
-            CliptoolsError::DataNotFound => 1,
-            CliptoolsError::InternalError => 1,
-            CliptoolsError::ArgumentError(_) => 2,
-            CliptoolsError::JsonError(_) => 2,
-            CliptoolsError::Utf8Error => 2,
+    if let Some(max) = matches.value_of("max-size") {
+        let max = parse_count(max)?;
+        let size = map.values().map(Vec::len).sum::<usize>();
+        if size > max {
+            return Err(CliptoolsError::TooLarge(size, max).into());
         }
     }
+
+    if matches.is_present("merge") {
+        cliptools::merge_existing(board, &mut map)?;
+    }
+
+    if matches.is_present("dry-run") {
+        for line in cliptools::summarize(&map, aliases) {
+            eprintln!("{}", line);
+        }
+        return Ok(());
+    }
+
+    let stats = matches.is_present("stats").then(|| {
+        let sizes =
+            map.iter().map(|(ct, data)| (show_ct(ct, aliases), data.len())).collect::<Vec<_>>();
+        let sizes = sizes.iter().map(|(name, size)| (name.as_str(), *size)).collect::<Vec<_>>();
+        Stats::new(&sizes)
+    });
+    if matches.is_present("clear-first") {
+        cliptools::clear(board)?;
+    }
+    if matches.is_present("atomic") {
+        cliptools::set_verified(board, map, aliases)?;
+    } else {
+        cliptools::set(board, map.into_iter().collect())?;
+    }
+    if let Some(stats) = stats {
+        eprintln!("{}", stats.to_json_line());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{build_app, read_data};
+
+    #[test]
+    fn table_input() {
+        let matches = build_app()
+            .get_matches_from_safe(&["cliptools", "copy", "--table", "--value", "a,b"])
+            .unwrap();
+        let copy = matches.subcommand_matches("copy").unwrap();
+        assert_eq!(read_data(copy).unwrap(), b"a,b");
+    }
 }
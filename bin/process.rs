@@ -0,0 +1,59 @@
+//! Running user-provided commands through the system shell.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use cliptools::CliptoolsError;
+
+/// Builds a command running `command` through the shell (`sh -c`, or `cmd /C` on Windows).
+fn shell(command: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut cmd = Command::new(shell);
+    cmd.arg(flag).arg(command);
+    cmd
+}
+
+/// Runs a shell command with `input` on its stdin, and waits for it to exit.
+pub fn run_with_input(command: &str, input: &[u8]) -> Result<()> {
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("cannot run {}", command))
+        .context(CliptoolsError::InternalError)?;
+    let written = child.stdin.take().expect("stdin is piped").write_all(input);
+    let status = child.wait().context(CliptoolsError::InternalError)?;
+    match written {
+        // The command doesn't have to read its input.
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => {
+            return Err(anyhow::Error::msg(format!("cannot write to {}: {}", command, e))
+                .context(CliptoolsError::InternalError));
+        },
+        _ => {},
+    }
+    if !status.success() {
+        return Err(anyhow::Error::msg(format!("{} exited with {}", command, status))
+            .context(CliptoolsError::InternalError));
+    }
+    Ok(())
+}
+
+/// Runs a shell command and returns its stdout. If the command fails, its stderr is included in
+/// the error; otherwise it is passed through.
+pub fn run_capturing(command: &str) -> Result<Vec<u8>> {
+    let output = shell(command)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("cannot run {}", command))
+        .context(CliptoolsError::InternalError)?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        let mut msg = format!("{} exited with {}", command, output.status);
+        if !stderr.trim().is_empty() {
+            msg = format!("{}: {}", msg, stderr.trim_end());
+        }
+        return Err(anyhow::Error::msg(msg).context(CliptoolsError::InternalError));
+    }
+    eprint!("{}", stderr);
+    Ok(output.stdout)
+}
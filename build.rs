@@ -0,0 +1,35 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Records build metadata for `cliptools --version-json`.
+fn main() {
+    let target = std::env::var("TARGET").expect("cargo sets TARGET for build scripts");
+    println!("cargo:rustc-env=CLIPTOOLS_TARGET={}", target);
+
+    let commit = Command::new("git")
+        .args(&["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=CLIPTOOLS_GIT_COMMIT={}", commit.trim());
+    }
+    // Once a file is watched, cargo stops rerunning this script on any change, so watch every
+    // file a commit can change: HEAD when switching branches, and the ref of the current branch
+    // when committing, which is in packed-refs after `git gc`.
+    println!("cargo:rerun-if-changed=build.rs");
+    let head = Path::new(".git/HEAD");
+    if let Ok(contents) = std::fs::read_to_string(head) {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        if let Some(reference) = contents.trim().strip_prefix("ref: ") {
+            let path = Path::new(".git").join(reference);
+            if path.exists() {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+        if Path::new(".git/packed-refs").exists() {
+            println!("cargo:rerun-if-changed=.git/packed-refs");
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! User-defined content type aliases.
+//!
+//! Aliases are read from `$XDG_CONFIG_HOME/cliptools/aliases.toml` (or
+//! `~/.config/cliptools/aliases.toml`), which maps friendly names to system content types:
+//!
+//! ```toml
+//! screenshot = "image/png"
+//! ```
+//!
+//! Aliases can also be given on the command line, as `name=type`. These take precedence over both
+//! the config file and the builtin aliases.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::error::CliptoolsError;
+
+#[derive(Debug, Default)]
+pub struct Aliases {
+    aliases: BTreeMap<String, String>,
+    inline: BTreeMap<String, String>,
+    strict: bool,
+}
+
+impl Aliases {
+    /// Loads aliases from the user's config file. A missing file yields an empty alias set.
+    pub fn load() -> Result<Aliases> {
+        match config_path() {
+            Some(path) if path.is_file() => {
+                let contents = std::fs::read_to_string(&path).with_context(|| {
+                    CliptoolsError::ConfigError(format!("cannot read {}", path.display()))
+                })?;
+                Aliases::parse(&contents).with_context(|| {
+                    CliptoolsError::ConfigError(format!("malformed {}", path.display()))
+                })
+            },
+            _ => Ok(Aliases::default()),
+        }
+    }
+
+    fn parse(contents: &str) -> Result<Aliases> {
+        let table: toml::value::Table = toml::from_str(contents)?;
+        let aliases = table
+            .into_iter()
+            .map(|(name, value)| match value {
+                toml::Value::String(s) => Ok((name.to_ascii_lowercase(), s)),
+                other => Err(anyhow::anyhow!(
+                    "expected a string for alias {}, found a {}",
+                    name,
+                    other.type_str()
+                )),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Aliases { aliases, inline: BTreeMap::new(), strict: false })
+    }
+
+    /// Adds an alias given on the command line, as `name=type`.
+    pub fn add_inline(&mut self, spec: &str) -> Result<()> {
+        match spec.split_once('=') {
+            Some((name, system_type)) if !name.is_empty() && !system_type.is_empty() => {
+                self.inline.insert(name.to_ascii_lowercase(), system_type.into());
+                Ok(())
+            },
+            _ => Err(CliptoolsError::ArgumentError(format!(
+                "invalid alias {}; expected name=type",
+                spec
+            ))
+            .into()),
+        }
+    }
+
+    /// Returns the system type for the alias `name` if it was given on the command line. Such
+    /// aliases override builtin ones.
+    pub fn resolve_inline(&self, name: &str) -> Option<&str> {
+        self.inline.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    /// Returns the system type for the alias `name`, if defined.
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        self.resolve_inline(name)
+            .or_else(|| self.aliases.get(&name.to_ascii_lowercase()).map(String::as_str))
+    }
+
+    /// Makes type resolution strict: custom types prefixed with an at sign (@) are rejected, so
+    /// that only aliases are accepted.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Returns the alias for the system type `system_type`, if there is one.
+    pub fn name_of(&self, system_type: &str) -> Option<&str> {
+        self.inline
+            .iter()
+            .chain(&self.aliases)
+            .find(|(_, v)| v.as_str() == system_type)
+            .map(|(k, _)| k.as_str())
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+    Some(config_dir.join("cliptools").join("aliases.toml"))
+}
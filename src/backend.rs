@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use arboard::{Clipboard, ContentType, ImageData};
+
+/// A clipboard to operate on, on systems that have several (such as X11's primary selection).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+    Secondary,
+}
+
+impl Selection {
+    /// Names accepted by [`Selection::from_name`].
+    pub const NAMES: &'static [&'static str] = &["clipboard", "primary", "secondary"];
+
+    pub fn from_name(name: &str) -> Option<Selection> {
+        match name.to_ascii_lowercase().as_str() {
+            "clipboard" => Some(Selection::Clipboard),
+            "primary" => Some(Selection::Primary),
+            "secondary" => Some(Selection::Secondary),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Selection::Clipboard => "clipboard",
+            Selection::Primary => "primary",
+            Selection::Secondary => "secondary",
+        }
+    }
+}
+
+/// The subset of clipboard operations used by cliptools.
+///
+/// This is implemented by [`arboard::Clipboard`], and can be implemented by in-memory fakes for
+/// testing.
+pub trait ClipboardBackend {
+    fn get_text(&mut self) -> Result<String, arboard::Error>;
+
+    fn get_content_for_type(&mut self, ct: &ContentType) -> Result<Vec<u8>, arboard::Error>;
+
+    fn get_content_types(&mut self) -> Result<Vec<String>, arboard::Error>;
+
+    fn set_content_types(
+        &mut self,
+        map: HashMap<ContentType, Vec<u8>>,
+    ) -> Result<(), arboard::Error>;
+
+    /// Converts a native type name, as returned by `get_content_types`, to a content type.
+    fn normalize_content_type(&mut self, s: String) -> ContentType;
+
+    /// Removes all contents from the clipboard. By default, this sets an empty set of
+    /// representations.
+    fn clear(&mut self) -> Result<(), arboard::Error> {
+        self.set_content_types(HashMap::new())
+    }
+
+    fn get_image(&mut self) -> Result<ImageData<'static>, arboard::Error> {
+        Err(arboard::Error::ClipboardNotSupported)
+    }
+
+    fn set_image(&mut self, _image: ImageData) -> Result<(), arboard::Error> {
+        Err(arboard::Error::ClipboardNotSupported)
+    }
+
+    /// Describes the application owning the clipboard, if the backend can tell.
+    fn owner(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Selects the clipboard that subsequent operations apply to. By default, only the main
+    /// clipboard is supported.
+    fn select(&mut self, selection: Selection) -> Result<(), arboard::Error> {
+        match selection {
+            Selection::Clipboard => Ok(()),
+            _ => Err(arboard::Error::ClipboardNotSupported),
+        }
+    }
+}
+
+impl ClipboardBackend for Clipboard {
+    fn get_text(&mut self) -> Result<String, arboard::Error> {
+        Clipboard::get_text(self)
+    }
+
+    fn get_content_for_type(&mut self, ct: &ContentType) -> Result<Vec<u8>, arboard::Error> {
+        Clipboard::get_content_for_type(self, ct)
+    }
+
+    fn get_content_types(&mut self) -> Result<Vec<String>, arboard::Error> {
+        Clipboard::get_content_types(self)
+    }
+
+    fn set_content_types(
+        &mut self,
+        map: HashMap<ContentType, Vec<u8>>,
+    ) -> Result<(), arboard::Error> {
+        Clipboard::set_content_types(self, map)
+    }
+
+    fn normalize_content_type(&mut self, s: String) -> ContentType {
+        Clipboard::normalize_content_type(self, s)
+    }
+
+    fn clear(&mut self) -> Result<(), arboard::Error> {
+        Clipboard::clear(self)
+    }
+
+    fn get_image(&mut self) -> Result<ImageData<'static>, arboard::Error> {
+        Clipboard::get_image(self)
+    }
+
+    fn set_image(&mut self, image: ImageData) -> Result<(), arboard::Error> {
+        Clipboard::set_image(self, image)
+    }
+
+    fn owner(&mut self) -> Option<String> {
+        clipboard_owner()
+    }
+}
+
+/// Native name under which [`SystemClipboard`] lists the text of the primary and secondary
+/// selections.
+const SELECTION_TEXT_TYPE: &str = "text/plain;charset=utf-8";
+
+/// The system clipboard, operating on the selection chosen with [`ClipboardBackend::select`].
+///
+/// The primary and secondary selections exist on X11, and the primary one on some Wayland
+/// compositors. arboard only exposes their text, so in those selections, only text can be read
+/// and set: other formats are dropped when setting contents.
+pub struct SystemClipboard {
+    clipboard: Clipboard,
+    selection: Selection,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Result<SystemClipboard, arboard::Error> {
+        Ok(SystemClipboard { clipboard: Clipboard::new()?, selection: Selection::Clipboard })
+    }
+
+    fn in_clipboard(&self) -> bool {
+        self.selection == Selection::Clipboard
+    }
+
+    /// Reads the text of the selected primary or secondary selection.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn selection_text(&mut self) -> Result<String, arboard::Error> {
+        use arboard::ClipboardExtLinux;
+        self.clipboard.get_text_with_clipboard(linux_kind(self.selection))
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    fn selection_text(&mut self) -> Result<String, arboard::Error> {
+        Err(arboard::Error::ClipboardNotSupported)
+    }
+
+    /// Sets the text of the selected primary or secondary selection.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn set_selection_text(&mut self, text: String) -> Result<(), arboard::Error> {
+        use arboard::ClipboardExtLinux;
+        self.clipboard.set_text_with_clipboard(text, linux_kind(self.selection))
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    fn set_selection_text(&mut self, _text: String) -> Result<(), arboard::Error> {
+        Err(arboard::Error::ClipboardNotSupported)
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn linux_kind(selection: Selection) -> arboard::LinuxClipboardKind {
+    match selection {
+        Selection::Clipboard => arboard::LinuxClipboardKind::Clipboard,
+        Selection::Primary => arboard::LinuxClipboardKind::Primary,
+        Selection::Secondary => arboard::LinuxClipboardKind::Secondary,
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn get_text(&mut self) -> Result<String, arboard::Error> {
+        if self.in_clipboard() {
+            return self.clipboard.get_text();
+        }
+        self.selection_text()
+    }
+
+    fn get_content_for_type(&mut self, ct: &ContentType) -> Result<Vec<u8>, arboard::Error> {
+        match ct {
+            _ if self.in_clipboard() => self.clipboard.get_content_for_type(ct),
+            ContentType::Text => self.selection_text().map(String::into_bytes),
+            _ => Err(arboard::Error::ContentNotAvailable),
+        }
+    }
+
+    fn get_content_types(&mut self) -> Result<Vec<String>, arboard::Error> {
+        if self.in_clipboard() {
+            return self.clipboard.get_content_types();
+        }
+        match self.selection_text() {
+            Ok(text) if text.is_empty() => Err(arboard::Error::ContentNotAvailable),
+            Ok(_) => Ok(vec![SELECTION_TEXT_TYPE.into()]),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn set_content_types(
+        &mut self,
+        mut map: HashMap<ContentType, Vec<u8>>,
+    ) -> Result<(), arboard::Error> {
+        if self.in_clipboard() {
+            return self.clipboard.set_content_types(map);
+        }
+        if map.is_empty() {
+            return self.set_selection_text(String::new());
+        }
+        let text = map.remove(&ContentType::Text).ok_or(arboard::Error::ClipboardNotSupported)?;
+        let text = String::from_utf8(text).map_err(|_| arboard::Error::ConversionFailure)?;
+        self.set_selection_text(text)
+    }
+
+    fn normalize_content_type(&mut self, s: String) -> ContentType {
+        if !self.in_clipboard() && s == SELECTION_TEXT_TYPE {
+            return ContentType::Text;
+        }
+        self.clipboard.normalize_content_type(s)
+    }
+
+    fn clear(&mut self) -> Result<(), arboard::Error> {
+        if self.in_clipboard() {
+            return self.clipboard.clear();
+        }
+        self.set_selection_text(String::new())
+    }
+
+    fn get_image(&mut self) -> Result<ImageData<'static>, arboard::Error> {
+        if self.in_clipboard() {
+            return self.clipboard.get_image();
+        }
+        Err(arboard::Error::ClipboardNotSupported)
+    }
+
+    fn set_image(&mut self, image: ImageData) -> Result<(), arboard::Error> {
+        if self.in_clipboard() {
+            return self.clipboard.set_image(image);
+        }
+        Err(arboard::Error::ClipboardNotSupported)
+    }
+
+    fn owner(&mut self) -> Option<String> {
+        self.clipboard.owner()
+    }
+
+    /// Selects a clipboard. The primary and secondary selections are only available on Linux and
+    /// other systems using X11 or Wayland.
+    fn select(&mut self, selection: Selection) -> Result<(), arboard::Error> {
+        if selection != Selection::Clipboard && !cfg!(all(unix, not(target_os = "macos"))) {
+            return Err(arboard::Error::ClipboardNotSupported);
+        }
+        self.selection = selection;
+        Ok(())
+    }
+}
+
+/// Describes the application owning the clipboard, by its executable and the title of its window.
+/// The owner is usually a hidden window, without a title.
+#[cfg(windows)]
+fn clipboard_owner() -> Option<String> {
+    use winapi::shared::minwindef::{DWORD, FALSE};
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winbase::QueryFullProcessImageNameW;
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+    use winapi::um::winuser::{GetClipboardOwner, GetWindowTextW, GetWindowThreadProcessId};
+
+    // SAFETY: the window handle is only passed to functions that accept stale handles, and
+    // buffers are passed with their actual size.
+    unsafe {
+        let window = GetClipboardOwner();
+        if window.is_null() {
+            return None;
+        }
+        let mut title = [0u16; 256];
+        let len = GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32);
+        let title = String::from_utf16_lossy(&title[..len.max(0) as usize]);
+
+        let mut pid: DWORD = 0;
+        GetWindowThreadProcessId(window, &mut pid);
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        let mut path = [0u16; 1024];
+        let mut len = path.len() as DWORD;
+        let exe = if process.is_null() {
+            None
+        } else {
+            let ok = QueryFullProcessImageNameW(process, 0, path.as_mut_ptr(), &mut len);
+            CloseHandle(process);
+            if ok != 0 {
+                Some(String::from_utf16_lossy(&path[..len as usize]))
+            } else {
+                None
+            }
+        };
+        match (exe, title.is_empty()) {
+            (Some(exe), true) => Some(exe),
+            (Some(exe), false) => Some(format!("{} ({})", exe, title)),
+            (None, false) => Some(title),
+            (None, true) => Some(format!("process {}", pid)),
+        }
+    }
+}
+
+/// Describes the application owning the clipboard. Other backends don't expose the owner: on
+/// X11, it is a window that usually doesn't identify its application, and Wayland and macOS don't
+/// tell it at all.
+#[cfg(not(windows))]
+fn clipboard_owner() -> Option<String> {
+    None
+}
+
+/// Describes the clipboard backend used on this platform. arboard doesn't report which backend
+/// it selected on Linux, so this mirrors its selection logic: Wayland's data-control protocol when
+/// a Wayland compositor is available, X11 otherwise.
+pub fn backend_name() -> &'static str {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "macos")] {
+            "macOS pasteboard"
+        } else if #[cfg(windows)] {
+            "Win32 clipboard"
+        } else {
+            if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                "Wayland (data-control)"
+            } else if std::env::var_os("DISPLAY").is_some() {
+                "X11"
+            } else {
+                "unknown (neither WAYLAND_DISPLAY nor DISPLAY is set)"
+            }
+        }
+    }
+}
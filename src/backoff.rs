@@ -0,0 +1,166 @@
+//! Retrying clipboard operations that fail because the clipboard is busy.
+//!
+//! On X11, reading the clipboard means asking the selection owner for the data, which fails if
+//! the owner is changing or doesn't answer in time, for instance while another program is copying.
+//! Such failures are transient, while others (unsupported types, missing data) are not, so only
+//! the former are retried.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use arboard::{ContentType, ImageData};
+
+use crate::backend::{ClipboardBackend, Selection};
+use crate::types::duplicate_ct;
+
+/// Number of attempts made by `--backoff-on-busy`.
+pub const DEFAULT_ATTEMPTS: u32 = 6;
+
+/// Delay before the first retry. It is doubled after each attempt.
+const INITIAL_DELAY: Duration = Duration::from_millis(10);
+
+/// Fragments of the messages of errors caused by contention over the clipboard, in lowercase.
+/// arboard reports these as free-form messages, which differ between backends.
+const BUSY_MESSAGES: &[&str] = &[
+    "occupied",
+    "held by an other party",
+    "busy",
+    "selection owner",
+    "timed out",
+    "timeout",
+    "resource temporarily unavailable",
+    "try again",
+];
+
+/// Tells whether an error message is about the clipboard being busy, so that the operation may
+/// succeed if retried.
+pub fn is_busy_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    BUSY_MESSAGES.iter().any(|fragment| message.contains(fragment))
+}
+
+/// Tells whether a clipboard error is caused by the clipboard being busy.
+pub fn is_busy(err: &arboard::Error) -> bool {
+    is_busy_message(&err.to_string())
+}
+
+/// A clipboard backend retrying operations that fail because the clipboard is busy, waiting
+/// exponentially longer between attempts. Other errors are returned immediately.
+pub struct Backoff<B> {
+    inner: B,
+    attempts: u32,
+}
+
+impl<B: ClipboardBackend> Backoff<B> {
+    /// Wraps `inner`, making up to `attempts` attempts for each operation. With a single attempt,
+    /// operations are never retried.
+    pub fn new(inner: B, attempts: u32) -> Backoff<B> {
+        Backoff { inner, attempts: attempts.max(1) }
+    }
+
+    fn retry<T>(
+        &mut self,
+        mut op: impl FnMut(&mut B) -> Result<T, arboard::Error>,
+    ) -> Result<T, arboard::Error> {
+        let mut delay = INITIAL_DELAY;
+        let mut attempt = 1;
+        loop {
+            match op(&mut self.inner) {
+                Err(e) if attempt < self.attempts && is_busy(&e) => {
+                    thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<B: ClipboardBackend> ClipboardBackend for Backoff<B> {
+    fn get_text(&mut self) -> Result<String, arboard::Error> {
+        self.retry(|board| board.get_text())
+    }
+
+    fn get_content_for_type(&mut self, ct: &ContentType) -> Result<Vec<u8>, arboard::Error> {
+        self.retry(|board| board.get_content_for_type(ct))
+    }
+
+    fn get_content_types(&mut self) -> Result<Vec<String>, arboard::Error> {
+        self.retry(|board| board.get_content_types())
+    }
+
+    fn set_content_types(
+        &mut self,
+        map: HashMap<ContentType, Vec<u8>>,
+    ) -> Result<(), arboard::Error> {
+        self.retry(|board| {
+            board.set_content_types(
+                map.iter().map(|(ct, v)| (duplicate_ct(ct), v.clone())).collect(),
+            )
+        })
+    }
+
+    fn normalize_content_type(&mut self, s: String) -> ContentType {
+        self.inner.normalize_content_type(s)
+    }
+
+    fn clear(&mut self) -> Result<(), arboard::Error> {
+        self.retry(|board| board.clear())
+    }
+
+    fn get_image(&mut self) -> Result<ImageData<'static>, arboard::Error> {
+        self.retry(|board| board.get_image())
+    }
+
+    fn set_image(&mut self, image: ImageData) -> Result<(), arboard::Error> {
+        self.retry(|board| {
+            board.set_image(ImageData {
+                width: image.width,
+                height: image.height,
+                bytes: Cow::Borrowed(&image.bytes),
+            })
+        })
+    }
+
+    fn owner(&mut self) -> Option<String> {
+        self.inner.owner()
+    }
+
+    fn select(&mut self, selection: Selection) -> Result<(), arboard::Error> {
+        self.inner.select(selection)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_busy_message;
+
+    #[test]
+    fn busy_messages() {
+        let busy = [
+            "The native clipboard is not accessible due to being held by an other party.",
+            "Clipboard is occupied",
+            "selection owner unavailable",
+            "Timed out waiting for the selection owner to respond",
+            "Resource temporarily unavailable (os error 11)",
+            "OpenClipboard failed: access denied, try again later",
+        ];
+        for message in &busy {
+            assert!(is_busy_message(message), "{}", message);
+        }
+        let fatal = [
+            "The clipboard contents were not available in the requested format or the clipboard \
+             is empty.",
+            "The selected clipboard is not supported with the current system configuration.",
+            "The image or the text that was about the be transferred to/from the clipboard could \
+             not be converted to the appropriate format.",
+            "Unknown error while interacting with the clipboard: connection refused",
+        ];
+        for message in &fatal {
+            assert!(!is_busy_message(message), "{}", message);
+        }
+    }
+}
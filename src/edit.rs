@@ -0,0 +1,63 @@
+//! Editing clipboard text in the user's editor.
+
+use std::array::IntoIter;
+use std::io::Write;
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use arboard::ContentType;
+
+use crate::backend::ClipboardBackend;
+use crate::error::CliptoolsError;
+
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Returns the user's editor command, from `$VISUAL` or `$EDITOR`.
+fn editor() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.into())
+}
+
+/// Opens the clipboard text in the user's editor, and copies the result back to the clipboard.
+/// The clipboard is left untouched if the editor fails or the text is not modified. Returns
+/// whether the clipboard was modified.
+pub fn edit_text<B: ClipboardBackend>(board: &mut B) -> Result<bool> {
+    // An empty or non-text clipboard can be edited from scratch.
+    let original = board.get_text().unwrap_or_default();
+
+    let mut file = tempfile::Builder::new()
+        .prefix("cliptools-")
+        .suffix(".txt")
+        .tempfile()
+        .context(CliptoolsError::InternalError)?;
+    file.write_all(original.as_bytes()).context(CliptoolsError::InternalError)?;
+    file.flush().context(CliptoolsError::InternalError)?;
+
+    // Editor commands can include arguments, like `code --wait`.
+    let editor = editor();
+    let mut words = editor.split_whitespace();
+    let program = words.next().expect("editor command is not empty");
+    let status = Command::new(program)
+        .args(words)
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("cannot run editor {}", editor))
+        .context(CliptoolsError::InternalError)?;
+    if !status.success() {
+        return Err(anyhow::Error::msg(format!("editor exited with {}", status))
+            .context(CliptoolsError::InternalError));
+    }
+
+    let edited = std::fs::read(file.path()).context(CliptoolsError::InternalError)?;
+    if edited == original.as_bytes() {
+        return Ok(false);
+    }
+    crate::set(board, IntoIter::new([(ContentType::Text, edited)]).collect())?;
+    Ok(true)
+}
@@ -0,0 +1,97 @@
+//! Text encodings and compression of binary data.
+
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::CliptoolsError;
+
+/// Decodes standard base64, ignoring whitespace so that wrapped lines are accepted.
+pub fn decode_base64(text: &[u8]) -> Result<Vec<u8>> {
+    let compact = text.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect::<Vec<_>>();
+    base64::decode(&compact).map_err(|e| CliptoolsError::Base64Error(e.to_string()).into())
+}
+
+/// Compresses data with gzip.
+pub fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .map_err(|e| CliptoolsError::CompressionError(e.to_string()).into())
+}
+
+/// Decompresses gzip data.
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    GzDecoder::new(data)
+        .read_to_end(&mut out)
+        .map_err(|e| CliptoolsError::CompressionError(e.to_string()))?;
+    Ok(out)
+}
+
+/// Guesses the character encoding of text: from its byte order mark if it has one, UTF-8 if it is
+/// valid UTF-8, and from the frequency of characters otherwise (see [`chardetng`]). Legacy
+/// single-byte encodings are hard to tell apart, so the guess may be wrong for short texts.
+pub fn detect_charset(data: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(data) {
+        return encoding;
+    }
+    if std::str::from_utf8(data).is_ok() {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(data, true);
+    detector.guess(None, true)
+}
+
+/// Decodes text in the given encoding, replacing malformed sequences with U+FFFD. A byte order
+/// mark is removed, and overrides `charset`.
+pub fn to_utf8(data: &[u8], charset: &'static Encoding) -> String {
+    charset.decode(data).0.into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use encoding_rs::{UTF_16LE, UTF_8, WINDOWS_1252};
+
+    use super::{decode_base64, detect_charset, gunzip, gzip, to_utf8};
+
+    #[test]
+    fn base64() {
+        assert_eq!(decode_base64(b"aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64(b"aGVs\r\nbG8=\n").unwrap(), b"hello");
+        assert!(decode_base64(b"aGVsbG8").is_err());
+        assert!(decode_base64(b"a!==").is_err());
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let data = "caf\u{e9} ".repeat(1000).into_bytes();
+        let compressed = gzip(&data).unwrap();
+        assert!(compressed.starts_with(b"\x1f\x8b") && compressed.len() < data.len());
+        assert_eq!(gunzip(&compressed).unwrap(), data);
+        assert_eq!(gunzip(&gzip(b"").unwrap()).unwrap(), b"");
+        assert!(gunzip(b"not gzip").is_err());
+        assert!(gunzip(&compressed[..compressed.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn charsets() {
+        assert_eq!(detect_charset("caf\u{e9}".as_bytes()), UTF_8);
+        assert_eq!(detect_charset(b"\xff\xfea\0"), UTF_16LE);
+        assert_eq!(to_utf8(b"\xff\xfea\0", UTF_16LE), "a");
+
+        let latin = b"Le caf\xe9 \xe9tait d\xe9j\xe0 servi, tr\xe8s chaud, \xe0 la terrasse.";
+        assert_eq!(detect_charset(latin), WINDOWS_1252);
+        assert_eq!(
+            to_utf8(latin, WINDOWS_1252),
+            "Le caf\u{e9} \u{e9}tait d\u{e9}j\u{e0} servi, tr\u{e8}s chaud, \u{e0} la terrasse."
+        );
+    }
+}
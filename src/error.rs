@@ -0,0 +1,73 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CliptoolsError {
+    #[error("data not found")]
+    DataNotFound,
+    #[error("usage: {0}")]
+    ArgumentError(String),
+    #[error("data in clipboard is not valid UTF-8; try using `--binary always`")]
+    Utf8Error,
+    #[error("data in clipboard looks binary; try using `--binary always`")]
+    BinaryContent,
+    #[error("invalid JSON input: {0}")]
+    JsonError(String),
+    #[error("internal error")]
+    InternalError,
+    #[error("invalid URL encoding: {0}")]
+    UrlEncodingError(String),
+    #[error("invalid URL: {0}")]
+    UrlError(String),
+    #[error("invalid base64: {0}")]
+    Base64Error(String),
+    #[error("{0}")]
+    FileError(String),
+    #[error("invalid image: {0}")]
+    ImageError(String),
+    #[error("invalid framed input: {0}")]
+    ProtocolError(String),
+    #[error("data in clipboard is not valid {0}")]
+    TypeMismatch(String),
+    #[error("data in clipboard is not ASCII: {0}")]
+    NonAsciiError(String),
+    #[error("data is {0} bytes, more than the limit of {1} bytes")]
+    TooLarge(usize, usize),
+    #[error("invalid compressed data: {0}")]
+    CompressionError(String),
+    #[error("malformed data in clipboard: {0}")]
+    InvalidContent(String),
+    #[error("timed out waiting for the clipboard to change")]
+    Timeout,
+    #[error("invalid configuration: {0}")]
+    ConfigError(String),
+}
+
+impl CliptoolsError {
+    /// Converts an error into the exit code.
+    ///  - 1 for missing or malformed data, or clipboard errors
+    ///  - 2 for user errors
+    ///  - 3 for timeouts
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliptoolsError::DataNotFound => 1,
+            CliptoolsError::InternalError => 1,
+            CliptoolsError::InvalidContent(_) => 1,
+            CliptoolsError::ArgumentError(_) => 2,
+            CliptoolsError::JsonError(_) => 2,
+            CliptoolsError::Utf8Error => 2,
+            CliptoolsError::BinaryContent => 2,
+            CliptoolsError::ConfigError(_) => 2,
+            CliptoolsError::UrlEncodingError(_) => 2,
+            CliptoolsError::UrlError(_) => 2,
+            CliptoolsError::ImageError(_) => 2,
+            CliptoolsError::Base64Error(_) => 2,
+            CliptoolsError::FileError(_) => 2,
+            CliptoolsError::ProtocolError(_) => 2,
+            CliptoolsError::TypeMismatch(_) => 2,
+            CliptoolsError::NonAsciiError(_) => 2,
+            CliptoolsError::TooLarge(_, _) => 2,
+            CliptoolsError::CompressionError(_) => 2,
+            CliptoolsError::Timeout => 3,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Escaping of text for embedding in source code or commands.
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Syntax {
+    Shell,
+    C,
+    Json,
+}
+
+impl Syntax {
+    /// Names accepted by [`Syntax::from_name`].
+    pub const NAMES: &'static [&'static str] = &["shell", "c", "json"];
+
+    pub fn from_name(name: &str) -> Option<Syntax> {
+        match name.to_ascii_lowercase().as_str() {
+            "shell" => Some(Syntax::Shell),
+            "c" => Some(Syntax::C),
+            "json" => Some(Syntax::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Quotes `text` as a string literal in the given syntax.
+pub fn escape(text: &str, syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Shell => escape_shell(text),
+        Syntax::C => escape_c(text),
+        Syntax::Json => serde_json::to_string(text).expect("strings can always be serialized"),
+    }
+}
+
+/// Single-quotes `text` for POSIX shells. Single quotes can't be escaped inside single-quoted
+/// strings, so they are written as `'\''` (closing the string, adding an escaped quote, and
+/// reopening it).
+fn escape_shell(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Double-quotes `text` as a C string literal. Bytes outside of printable ASCII are written as
+/// octal escapes, which unlike hex escapes can't swallow the characters that follow.
+fn escape_c(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for &b in text.as_bytes() {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7e => out.push(char::from(b)),
+            _ => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Renders arbitrary bytes as printable text in caret notation, like `cat -v`: control characters
+/// are written as `^` followed by a letter (`^A`, `^[`, `^?` for DEL), and bytes above 127 as `M-`
+/// followed by the notation for the byte without its high bit. Newlines and tabs are kept as-is.
+pub fn caret_notation(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len());
+    for &b in data {
+        let low = if b >= 0x80 {
+            out.push_str("M-");
+            b & 0x7f
+        } else {
+            b
+        };
+        match low {
+            b'\n' | b'\t' if b == low => out.push(char::from(b)),
+            0x00..=0x1f => {
+                out.push('^');
+                out.push(char::from(low + 0x40));
+            },
+            0x7f => out.push_str("^?"),
+            _ => out.push(char::from(low)),
+        }
+    }
+    out
+}
+
+/// Sequence with which terminals in bracketed paste mode start pasted text.
+const PASTE_START: &str = "\x1b[200~";
+/// Sequence with which terminals in bracketed paste mode end pasted text.
+const PASTE_END: &str = "\x1b[201~";
+
+/// Wraps `text` in the bracketed paste sequences, so that a terminal program in bracketed paste
+/// mode (such as a shell) treats it as pasted text, and doesn't run it as it is written. End
+/// sequences inside `text` are removed, since they would let the rest be interpreted as input.
+pub fn bracket_paste(text: &str) -> String {
+    let mut text = text.to_string();
+    while text.contains(PASTE_END) {
+        text = text.replace(PASTE_END, "");
+    }
+    format!("{}{}{}", PASTE_START, text, PASTE_END)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bracket_paste, caret_notation, escape, Syntax};
+
+    #[test]
+    fn shell() {
+        assert_eq!(escape("it's $HOME", Syntax::Shell), "'it'\\''s $HOME'");
+        assert_eq!(escape("", Syntax::Shell), "''");
+    }
+
+    #[test]
+    fn c() {
+        assert_eq!(escape("a \"b\"\\\n\t", Syntax::C), "\"a \\\"b\\\"\\\\\\n\\t\"");
+        assert_eq!(escape("\u{e9}\0", Syntax::C), "\"\\303\\251\\000\"");
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(escape("a \"b\"\n\u{1}", Syntax::Json), "\"a \\\"b\\\"\\n\\u0001\"");
+    }
+
+    #[test]
+    fn caret() {
+        assert_eq!(caret_notation(b"plain\ttext\n"), "plain\ttext\n");
+        assert_eq!(caret_notation(b"\0\x01\x1b[1m\x7f"), "^@^A^[[1m^?");
+        assert_eq!(caret_notation(b"\x89PNG\r\n"), "M-^IPNG^M\n");
+        assert_eq!(caret_notation("\u{e9}".as_bytes()), "M-CM-)");
+        assert_eq!(caret_notation(b"\x8a\xff"), "M-^JM-^?");
+    }
+
+    #[test]
+    fn bracketed_paste() {
+        assert_eq!(bracket_paste("ls\nrm x\n"), "\x1b[200~ls\nrm x\n\x1b[201~");
+        assert_eq!(bracket_paste("a\x1b[201~\nb\x1b[20\x1b[201~1~"), "\x1b[200~a\nb\x1b[201~");
+    }
+}
@@ -0,0 +1,204 @@
+//! Transfer of file lists, as file managers put in the clipboard when copying files.
+//!
+//! On Windows, these are stored as `CF_HDROP` data: a `DROPFILES` header followed by the
+//! NUL-terminated paths, with an empty path marking the end. On macOS, each file is a
+//! `public.file-url` pasteboard entry holding a `file://` URL.
+
+use std::array::IntoIter;
+
+use anyhow::Result;
+use arboard::ContentType;
+#[cfg(any(target_os = "macos", test))]
+use url::Url;
+
+use crate::backend::ClipboardBackend;
+use crate::error::CliptoolsError;
+
+/// Name of the `files` pseudo-type, which isn't a content type of its own.
+pub const FILES_TYPE: &str = "files";
+
+/// Native name of the clipboard format holding file lists.
+#[cfg(windows)]
+const SYSTEM_TYPE: &str = "CF_HDROP";
+#[cfg(target_os = "macos")]
+const SYSTEM_TYPE: &str = "public.file-url";
+
+/// Size of the `DROPFILES` structure preceding the paths.
+#[cfg(any(windows, test))]
+const DROPFILES_SIZE: usize = 20;
+
+/// Returns the paths of the files in the clipboard.
+#[cfg(windows)]
+pub fn get_files<B: ClipboardBackend>(board: &mut B) -> Result<Vec<String>> {
+    let data = crate::get(board, &ContentType::Custom(SYSTEM_TYPE.into()))?;
+    decode_hdrop(&data).map_err(|e| CliptoolsError::InvalidContent(e).into())
+}
+
+/// Encodes a list of files as the clipboard stores them, returning the content type and data.
+#[cfg(windows)]
+pub fn files_content(paths: &[String]) -> Result<(ContentType, Vec<u8>)> {
+    Ok((ContentType::Custom(SYSTEM_TYPE.into()), encode_hdrop(paths)))
+}
+
+/// Returns the paths of the files in the clipboard.
+#[cfg(target_os = "macos")]
+pub fn get_files<B: ClipboardBackend>(board: &mut B) -> Result<Vec<String>> {
+    let data = crate::get(board, &ContentType::Custom(SYSTEM_TYPE.into()))?;
+    let text = std::str::from_utf8(&data)
+        .map_err(|_| CliptoolsError::InvalidContent("file URL is not valid UTF-8".into()))?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| file_url_to_path(line.trim()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| CliptoolsError::InvalidContent(e).into())
+}
+
+/// Encodes a file as the pasteboard stores it, returning the content type and data. The pasteboard
+/// holds one file URL per item, and the clipboard backend only writes a single item, so only one
+/// file is supported.
+#[cfg(target_os = "macos")]
+pub fn files_content(paths: &[String]) -> Result<(ContentType, Vec<u8>)> {
+    let path = match paths {
+        [path] => path,
+        _ => {
+            return Err(CliptoolsError::ArgumentError(format!(
+                "expected exactly one path, got {}; the {} clipboard backend can only copy one \
+                 file at a time",
+                paths.len(),
+                crate::backend_name()
+            ))
+            .into())
+        },
+    };
+    Ok((ContentType::Custom(SYSTEM_TYPE.into()), path_to_file_url(path)?.into_bytes()))
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn get_files<B: ClipboardBackend>(_board: &mut B) -> Result<Vec<String>> {
+    Err(unsupported())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn files_content(_paths: &[String]) -> Result<(ContentType, Vec<u8>)> {
+    Err(unsupported())
+}
+
+/// Puts a list of files in the clipboard, as if they were copied in the file manager.
+pub fn set_files<B: ClipboardBackend>(board: &mut B, paths: &[String]) -> Result<()> {
+    crate::set(board, IntoIter::new([files_content(paths)?]).collect())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn unsupported() -> anyhow::Error {
+    CliptoolsError::ArgumentError(format!(
+        "the {} type is not supported by the {} clipboard backend",
+        FILES_TYPE,
+        crate::backend_name()
+    ))
+    .into()
+}
+
+/// Encodes paths as `CF_HDROP` data, using UTF-16 paths.
+#[cfg(any(windows, test))]
+fn encode_hdrop(paths: &[String]) -> Vec<u8> {
+    let mut data = Vec::new();
+    // pFiles (offset of the paths), pt.x, pt.y, fNC, fWide.
+    for field in &[DROPFILES_SIZE as u32, 0, 0, 0, 1] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    for path in paths {
+        path.encode_utf16().chain(Some(0)).for_each(|c| data.extend_from_slice(&c.to_le_bytes()));
+    }
+    data.extend_from_slice(&[0, 0]);
+    data
+}
+
+/// Decodes `CF_HDROP` data into a list of paths.
+#[cfg(any(windows, test))]
+fn decode_hdrop(data: &[u8]) -> Result<Vec<String>, String> {
+    if data.len() < DROPFILES_SIZE {
+        return Err("file list is truncated".into());
+    }
+    let field = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+    let offset = field(0) as usize;
+    let wide = field(16) != 0;
+    let list = data.get(offset..).ok_or("file list offset is out of bounds")?;
+    let paths = if wide {
+        let units =
+            list.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect::<Vec<_>>();
+        units
+            .split(|&c| c == 0)
+            .take_while(|path| !path.is_empty())
+            .map(|path| String::from_utf16(path).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        list.split(|&b| b == 0)
+            .take_while(|path| !path.is_empty())
+            .map(|path| String::from_utf8_lossy(path).into_owned())
+            .collect()
+    };
+    Ok(paths)
+}
+
+/// Converts a `file://` URL to the path it points to.
+#[cfg(any(target_os = "macos", test))]
+fn file_url_to_path(url: &str) -> Result<String, String> {
+    Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() == "file")
+        .and_then(|url| url.to_file_path().ok())
+        .map(|path| path.display().to_string())
+        .ok_or_else(|| format!("not a file URL: {}", url))
+}
+
+/// Converts a path to a `file://` URL, resolving it relative to the current directory. The file
+/// must exist.
+#[cfg(any(target_os = "macos", test))]
+fn path_to_file_url(path: &str) -> Result<String> {
+    let absolute = std::fs::canonicalize(path)
+        .map_err(|e| CliptoolsError::FileError(format!("cannot find {}: {}", path, e)))?;
+    Url::from_file_path(&absolute).map(String::from).map_err(|_| {
+        CliptoolsError::FileError(format!("cannot convert {} to a URL", absolute.display())).into()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_hdrop, encode_hdrop};
+    #[cfg(unix)]
+    use super::{file_url_to_path, path_to_file_url};
+
+    #[test]
+    fn hdrop() {
+        let paths = vec!["C:\\a.txt".to_string(), "D:\\caf\u{e9}\\b.png".to_string()];
+        let data = encode_hdrop(&paths);
+        assert_eq!(&data[..4], &[20, 0, 0, 0]);
+        assert_eq!(decode_hdrop(&data).unwrap(), paths);
+        assert_eq!(decode_hdrop(&encode_hdrop(&[])).unwrap(), Vec::<String>::new());
+        assert!(decode_hdrop(&data[..10]).is_err());
+
+        let mut ansi = data[..20].to_vec();
+        ansi[16] = 0;
+        ansi.extend_from_slice(b"C:\\a.txt\0C:\\b\0\0");
+        assert_eq!(decode_hdrop(&ansi).unwrap(), vec!["C:\\a.txt", "C:\\b"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn file_urls() {
+        assert_eq!(file_url_to_path("file:///tmp/a%20b.txt").unwrap(), "/tmp/a b.txt");
+        assert!(file_url_to_path("https://a.b/c").is_err());
+        assert!(file_url_to_path("not a url").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("caf\u{e9} 1.txt");
+        std::fs::write(&file, "").unwrap();
+        let url = path_to_file_url(file.to_str().unwrap()).unwrap();
+        assert!(url.starts_with("file:///") && url.ends_with("/caf%C3%A9%201.txt"));
+        assert_eq!(
+            file_url_to_path(&url).unwrap(),
+            std::fs::canonicalize(&file).unwrap().display().to_string()
+        );
+        assert!(path_to_file_url(dir.path().join("missing").to_str().unwrap()).is_err());
+    }
+}
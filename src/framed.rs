@@ -0,0 +1,107 @@
+//! A simple framed format for copying several representations, including binary ones, from a
+//! stream. The stream contains records of the form:
+//!
+//! ```text
+//! <type>\n<length in bytes>\n<data>
+//! ```
+//!
+//! where `<type>` is a cliptools alias. Records may be separated by a newline for readability.
+
+use anyhow::Result;
+use arboard::ContentType;
+use indexmap::IndexMap;
+
+use crate::aliases::Aliases;
+use crate::error::CliptoolsError;
+use crate::types::string_to_ct;
+
+/// Splits `input` after its first newline, returning the line without the newline.
+fn split_line(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let pos = input.iter().position(|&b| b == b'\n')?;
+    Some((&input[..pos], &input[pos + 1..]))
+}
+
+fn protocol_error(frame: usize, msg: &str) -> anyhow::Error {
+    CliptoolsError::ProtocolError(format!("frame {}: {}", frame, msg)).into()
+}
+
+/// Parses framed input into a map of content types to data, in the order of the frames.
+pub fn parse_framed(input: &[u8], aliases: &Aliases) -> Result<IndexMap<ContentType, Vec<u8>>> {
+    let mut map = IndexMap::new();
+    let mut rest = input;
+    let mut frame = 0;
+    while !rest.is_empty() {
+        frame += 1;
+        let (typ, after_type) =
+            split_line(rest).ok_or_else(|| protocol_error(frame, "missing newline after type"))?;
+        let typ = std::str::from_utf8(typ)
+            .map_err(|_| protocol_error(frame, "type is not valid UTF-8"))?
+            .trim_end_matches('\r');
+        let ct = string_to_ct(typ, aliases)
+            .ok_or_else(|| protocol_error(frame, &format!("unknown type: {}", typ)))?;
+
+        let (len, after_len) = split_line(after_type)
+            .ok_or_else(|| protocol_error(frame, "missing newline after length"))?;
+        let len = std::str::from_utf8(len)
+            .ok()
+            .and_then(|len| len.trim_end_matches('\r').parse::<usize>().ok())
+            .ok_or_else(|| protocol_error(frame, "invalid length"))?;
+        if after_len.len() < len {
+            return Err(protocol_error(
+                frame,
+                &format!("expected {} bytes of data, got {}", len, after_len.len()),
+            ));
+        }
+
+        if map.insert(ct, after_len[..len].to_vec()).is_some() {
+            return Err(protocol_error(frame, &format!("duplicate type: {}", typ)));
+        }
+        rest = &after_len[len..];
+        if rest.first() == Some(&b'\n') {
+            rest = &rest[1..];
+        }
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod test {
+    use arboard::ContentType;
+
+    use super::parse_framed;
+    use crate::aliases::Aliases;
+    use crate::error::CliptoolsError;
+
+    fn parse_error(input: &[u8]) -> String {
+        let err = parse_framed(input, &Aliases::default()).unwrap_err();
+        match err.downcast_ref::<CliptoolsError>() {
+            Some(CliptoolsError::ProtocolError(msg)) => msg.clone(),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn frames() {
+        let input = b"text\n5\nhello\npng\n3\n\x89\n\0html\r\n3\r\n<b>";
+        let map = parse_framed(input, &Aliases::default()).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&ContentType::Text], b"hello");
+        assert_eq!(map[&ContentType::Png], b"\x89\n\0");
+        assert_eq!(map[&ContentType::Html], b"<b>");
+        assert!(parse_framed(b"", &Aliases::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(parse_error(b"text"), "frame 1: missing newline after type");
+        assert_eq!(parse_error(b"text\n5"), "frame 1: missing newline after length");
+        assert_eq!(parse_error(b"text\n5\nhell"), "frame 1: expected 5 bytes of data, got 4");
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(parse_error(b"text\nfive\nhello"), "frame 1: invalid length");
+        assert_eq!(parse_error(b"text\n1\na\nnope\n1\nb"), "frame 2: unknown type: nope");
+        assert_eq!(parse_error(b"text\n1\natext\n1\nb"), "frame 2: duplicate type: text");
+    }
+}
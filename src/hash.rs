@@ -0,0 +1,85 @@
+//! Digests of clipboard contents.
+
+use digest::Digest;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::Sha256;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha1,
+    Md5,
+}
+
+impl HashAlgorithm {
+    /// Names accepted by [`HashAlgorithm::from_name`].
+    pub const NAMES: &'static [&'static str] = &["sha256", "sha1", "md5"];
+
+    pub fn from_name(name: &str) -> Option<HashAlgorithm> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "sha1" => Some(HashAlgorithm::Sha1),
+            "md5" => Some(HashAlgorithm::Md5),
+            _ => None,
+        }
+    }
+}
+
+/// Guesses the algorithm of a hex digest from its length.
+pub fn algorithm_of_digest(hex: &str) -> Option<HashAlgorithm> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        64 => Some(HashAlgorithm::Sha256),
+        40 => Some(HashAlgorithm::Sha1),
+        32 => Some(HashAlgorithm::Md5),
+        _ => None,
+    }
+}
+
+/// Tells whether `hex` is the digest of `data`, with the algorithm guessed from its length.
+pub fn digest_matches(hex: &str, data: &[u8]) -> bool {
+    algorithm_of_digest(hex)
+        .map_or(false, |algorithm| hex_digest(algorithm, data).eq_ignore_ascii_case(hex))
+}
+
+/// Returns the lowercase hex digest of `data`.
+pub fn hex_digest(algorithm: HashAlgorithm, data: &[u8]) -> String {
+    let digest = match algorithm {
+        HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        HashAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+        HashAlgorithm::Md5 => Md5::digest(data).to_vec(),
+    };
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{algorithm_of_digest, digest_matches, hex_digest, HashAlgorithm};
+
+    #[test]
+    fn digests() {
+        assert_eq!(
+            hex_digest(HashAlgorithm::Sha256, b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex_digest(HashAlgorithm::Sha1, b"abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(hex_digest(HashAlgorithm::Md5, b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+    }
+
+    #[test]
+    fn digest_lookup() {
+        assert_eq!(algorithm_of_digest(&"0".repeat(64)), Some(HashAlgorithm::Sha256));
+        assert_eq!(algorithm_of_digest(&"0".repeat(40)), Some(HashAlgorithm::Sha1));
+        assert_eq!(algorithm_of_digest(&"z".repeat(32)), None);
+        assert_eq!(algorithm_of_digest("abc"), None);
+        assert!(digest_matches("A9993E364706816ABA3E25717850C26C9CD0D89D", b"abc"));
+        assert!(digest_matches("900150983cd24fb0d6963f7d28e17f72", b"abc"));
+        assert!(!digest_matches("900150983cd24fb0d6963f7d28e17f72", b"abcd"));
+    }
+}
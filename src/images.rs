@@ -0,0 +1,52 @@
+//! Transfer of images through arboard's image API, which GUI applications expect instead of raw
+//! PNG bytes under a content type.
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use arboard::ImageData;
+use image::{DynamicImage, RgbaImage};
+
+use crate::backend::ClipboardBackend;
+use crate::error::CliptoolsError;
+
+/// Decodes an image file in any format supported by the `image` crate and puts it in the
+/// clipboard.
+pub fn copy_image_file<B: ClipboardBackend>(board: &mut B, path: &Path) -> Result<()> {
+    let image = image::open(path)
+        .with_context(|| {
+            CliptoolsError::ImageError(format!("cannot decode image {}", path.display()))
+        })?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+    let data = ImageData {
+        width: width as usize,
+        height: height as usize,
+        bytes: Cow::Owned(image.into_raw()),
+    };
+    board
+        .set_image(data)
+        .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::InternalError))
+}
+
+/// Writes the image in the clipboard to a file, in the format given by the file's extension.
+pub fn paste_image_file<B: ClipboardBackend>(board: &mut B, path: &Path) -> Result<()> {
+    let data = board
+        .get_image()
+        .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::DataNotFound))?;
+    let image = RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.into_owned())
+        .ok_or_else(|| {
+            CliptoolsError::ImageError("clipboard image data doesn't match its dimensions".into())
+        })?;
+    let image = DynamicImage::ImageRgba8(image);
+    let is_jpeg = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"));
+    // JPEG doesn't support transparency.
+    let saved = if is_jpeg { image.to_rgb8().save(path) } else { image.save(path) };
+    saved.with_context(|| {
+        CliptoolsError::ImageError(format!("cannot write image to {}", path.display()))
+    })
+}
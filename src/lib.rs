@@ -0,0 +1,417 @@
+//! Clipboard operations backing the `cliptools` command line tool.
+//!
+//! All functions report failures as [`anyhow::Error`]s carrying a [`CliptoolsError`] context,
+//! which can be recovered with `downcast_ref` to decide on an exit code.
+
+pub mod aliases;
+mod backend;
+pub mod backoff;
+pub mod edit;
+pub mod encoding;
+mod error;
+pub mod escape;
+pub mod files;
+pub mod framed;
+pub mod hash;
+pub mod images;
+pub mod markup;
+mod output;
+pub mod probe;
+pub mod selftest;
+pub mod sniff;
+pub mod table;
+pub mod text;
+mod types;
+pub mod urls;
+pub mod watch;
+
+use std::array::IntoIter;
+use std::collections::HashMap;
+use std::io::Read;
+
+use anyhow::{Context, Result};
+use arboard::ContentType;
+use indexmap::IndexMap;
+
+use crate::aliases::Aliases;
+pub use crate::backend::{backend_name, ClipboardBackend, Selection, SystemClipboard};
+pub use crate::error::CliptoolsError;
+pub use crate::output::{
+    format_size, is_broken_pipe, looks_binary, show_content, show_json_line, show_string,
+    size_totals, write_chunked, SizeTotals, Stats, TypeStats,
+};
+pub use crate::types::{
+    category, ct_to_extension, extension_to_ct, is_textual, normalize_system_type, show_ct,
+    string_to_ct, Category,
+};
+use crate::types::{duplicate_ct, type_matches};
+
+/// Version of the JSON formats read and written by cliptools. It is included in all JSON output,
+/// and may be given in JSON input, which is rejected if it has a version cliptools doesn't know.
+///
+/// Version 1 is a flat object mapping cliptools aliases to string contents.
+pub const JSON_VERSION: u64 = 1;
+
+/// Selects the clipboard to operate on, if the backend supports it.
+pub fn select<B: ClipboardBackend>(board: &mut B, selection: Selection) -> Result<()> {
+    board.select(selection).map_err(|_| {
+        CliptoolsError::ArgumentError(format!(
+            "the {} selection is not supported by the {} clipboard backend",
+            selection.name(),
+            backend_name()
+        ))
+        .into()
+    })
+}
+
+/// Fetches the clipboard contents for the given content type.
+pub fn get<B: ClipboardBackend>(board: &mut B, ct: &ContentType) -> Result<Vec<u8>> {
+    board
+        .get_content_for_type(ct)
+        .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::DataNotFound))
+}
+
+/// Fetches the clipboard contents as text.
+pub fn get_text<B: ClipboardBackend>(board: &mut B) -> Result<String> {
+    board
+        .get_text()
+        .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::DataNotFound))
+}
+
+/// Fetches the clipboard contents as text. If there is no plain text representation, derives it
+/// from HTML or RTF data instead, by stripping markup.
+pub fn get_text_with_fallback<B: ClipboardBackend>(board: &mut B) -> Result<String> {
+    let err = match get_text(board) {
+        Ok(text) => return Ok(text),
+        Err(err) => err,
+    };
+    let converters: [(ContentType, fn(&str) -> String); 2] =
+        [(ContentType::Html, markup::html_to_text), (ContentType::Rtf, markup::rtf_to_text)];
+    for (ct, convert) in &converters {
+        if let Some(val) =
+            board.get_content_for_type(ct).ok().and_then(|v| String::from_utf8(v).ok())
+        {
+            return Ok(convert(&val));
+        }
+    }
+    Err(err)
+}
+
+/// Replaces the clipboard contents with the given representations.
+pub fn set<B: ClipboardBackend>(board: &mut B, map: HashMap<ContentType, Vec<u8>>) -> Result<()> {
+    board
+        .set_content_types(map)
+        .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::InternalError))
+}
+
+/// Sets the contents of the clipboard like `set`, then reads each representation back, in the
+/// order of `map`, to check that it was stored unchanged. Reports the first one that wasn't.
+pub fn set_verified<B: ClipboardBackend>(
+    board: &mut B,
+    map: IndexMap<ContentType, Vec<u8>>,
+    aliases: &Aliases,
+) -> Result<()> {
+    let expected: Vec<_> = map.iter().map(|(ct, v)| (duplicate_ct(ct), v.clone())).collect();
+    set(board, map.into_iter().collect())?;
+    for (ct, data) in expected {
+        let problem = match board.get_content_for_type(&ct) {
+            Ok(actual) if actual == data => continue,
+            Ok(actual) => format!("read back {} bytes, expected {}", actual.len(), data.len()),
+            Err(e) => e.to_string(),
+        };
+        return Err(anyhow::Error::msg(format!(
+            "verification failed for {}: {}",
+            show_ct(&ct, aliases),
+            problem
+        ))
+        .context(CliptoolsError::InternalError));
+    }
+    Ok(())
+}
+
+/// Removes all contents from the clipboard.
+pub fn clear<B: ClipboardBackend>(board: &mut B) -> Result<()> {
+    board
+        .clear()
+        .map_err(|e| anyhow::Error::msg(e.to_string()).context(CliptoolsError::InternalError))
+}
+
+/// Returns the native names of the types currently in the clipboard. Some backends report an
+/// empty clipboard as an error, which is turned into an empty list here.
+fn content_types<B: ClipboardBackend>(board: &mut B) -> Result<Vec<String>> {
+    match board.get_content_types() {
+        Ok(types) => Ok(types),
+        Err(arboard::Error::ContentNotAvailable) => Ok(Vec::new()),
+        Err(e) => Err(anyhow::Error::msg(e.to_string()).context(CliptoolsError::InternalError)),
+    }
+}
+
+/// Fetches all the representations currently in the clipboard. Representations that can't be read
+/// back are skipped.
+pub fn get_all<B: ClipboardBackend>(board: &mut B) -> Result<HashMap<ContentType, Vec<u8>>> {
+    let types = content_types(board)?;
+    let mut map = HashMap::new();
+    for typ in types {
+        let ct = board.normalize_content_type(typ);
+        if !map.contains_key(&ct) {
+            if let Ok(val) = board.get_content_for_type(&ct) {
+                map.insert(ct, val);
+            }
+        }
+    }
+    Ok(map)
+}
+
+/// Adds the representations currently in the clipboard to `map`, unless `map` already has data
+/// for their type. Representations that can't be read back are skipped.
+pub fn merge_existing<B: ClipboardBackend>(
+    board: &mut B,
+    map: &mut IndexMap<ContentType, Vec<u8>>,
+) -> Result<()> {
+    for (ct, val) in get_all(board)? {
+        map.entry(ct).or_insert(val);
+    }
+    Ok(())
+}
+
+/// Describes the representations in `map`, one line per type giving its size, in the order of the
+/// map. This is what `copy --dry-run` prints.
+pub fn summarize(map: &IndexMap<ContentType, Vec<u8>>, aliases: &Aliases) -> Vec<String> {
+    map.iter().map(|(ct, data)| format!("{}: {} bytes", show_ct(ct, aliases), data.len())).collect()
+}
+
+/// Removes the representation for `ct` from the clipboard, keeping the others. Backends can't
+/// remove a single representation, so the others are read and set again: this is not atomic, and
+/// representations that can't be read back are lost.
+pub fn remove_type<B: ClipboardBackend>(board: &mut B, ct: &ContentType) -> Result<()> {
+    let mut map = get_all(board)?;
+    if map.remove(ct).is_none() {
+        return Err(CliptoolsError::DataNotFound.into());
+    }
+    if map.is_empty() {
+        clear(board)
+    } else {
+        set(board, map)
+    }
+}
+
+/// Copies all the representations in the `from` selection to the `to` selection, leaving `to`
+/// selected.
+pub fn copy_selection<B: ClipboardBackend>(
+    board: &mut B,
+    from: Selection,
+    to: Selection,
+) -> Result<()> {
+    select(board, from)?;
+    let map = get_all(board)?;
+    if map.is_empty() {
+        return Err(anyhow::Error::msg(format!("the {} selection is empty", from.name()))
+            .context(CliptoolsError::DataNotFound));
+    }
+    select(board, to)?;
+    set(board, map)
+}
+
+/// Exchanges the contents of the clipboard and the primary selection, leaving the clipboard
+/// selected. This is not atomic: both are read first, then written in turn.
+///
+/// If a backend can't set all the formats read from a selection at once, only the text is set.
+pub fn swap_selections<B: ClipboardBackend>(board: &mut B) -> Result<()> {
+    select(board, Selection::Clipboard)?;
+    let clipboard = get_all(board)?;
+    select(board, Selection::Primary)?;
+    let primary = get_all(board)?;
+    set_or_text(board, clipboard)?;
+    select(board, Selection::Clipboard)?;
+    set_or_text(board, primary)
+}
+
+/// Sets the given representations, falling back to only setting text if that fails.
+fn set_or_text<B: ClipboardBackend>(
+    board: &mut B,
+    map: HashMap<ContentType, Vec<u8>>,
+) -> Result<()> {
+    if map.len() <= 1 {
+        return set(board, map);
+    }
+    let text = map.get(&ContentType::Text).cloned();
+    match (board.set_content_types(map), text) {
+        (Ok(()), _) => Ok(()),
+        (Err(_), Some(text)) => set(board, IntoIter::new([(ContentType::Text, text)]).collect()),
+        (Err(e), None) => {
+            Err(anyhow::Error::msg(e.to_string()).context(CliptoolsError::InternalError))
+        },
+    }
+}
+
+/// Lists the types currently in the clipboard. With `system`, these are the platform's native
+/// names, as reported by the clipboard. Otherwise, they are cliptools aliases, which are sorted and
+/// deduplicated if `dedup` is set, or listed in the clipboard's order if not (so that an alias
+/// appears once per system type it stands for).
+///
+/// If `filter` is given, only names matching it are returned: it may be a glob (`image/*`) or a
+/// substring (`png`).
+pub fn list<B: ClipboardBackend>(
+    board: &mut B,
+    aliases: &Aliases,
+    system: bool,
+    filter: Option<&str>,
+    dedup: bool,
+) -> Result<Vec<String>> {
+    let keep = |name: &String| filter.map_or(true, |pattern| type_matches(pattern, name));
+    let types = content_types(board)?;
+    if system {
+        Ok(types.into_iter().map(|typ| typ.to_string()).filter(keep).collect())
+    } else {
+        let mut converted = types
+            .into_iter()
+            .map(|s| board.normalize_content_type(s))
+            .map(|ct| show_ct(&ct, aliases))
+            .filter(keep)
+            .collect::<Vec<_>>();
+        if dedup {
+            converted.sort();
+            converted.dedup();
+        }
+        Ok(converted)
+    }
+}
+
+/// Returns the number of distinct content types currently in the clipboard, counting system types
+/// that stand for the same content type once.
+pub fn count_types<B: ClipboardBackend>(board: &mut B, aliases: &Aliases) -> Result<usize> {
+    list(board, aliases, false, None, true).map(|types| types.len())
+}
+
+/// Returns the content types in `types` that are not currently in the clipboard.
+pub fn missing_types<'a, B: ClipboardBackend>(
+    board: &mut B,
+    types: &'a [ContentType],
+) -> Result<Vec<&'a ContentType>> {
+    let present = content_types(board)?
+        .into_iter()
+        .map(|typ| board.normalize_content_type(typ))
+        .collect::<Vec<_>>();
+    Ok(types.iter().filter(|ct| !present.contains(ct)).collect())
+}
+
+/// Reads JSON input, as accepted by `copy --json`, returning types in the order of the input. The
+/// input is either:
+///  - a map of cliptools aliases to string contents, which may have a `version` key;
+///  - an array of records of the form `{"type": ..., "content": ...}`, as printed by
+///    `paste --jsonl`. Records may have a `version` key, and an `"encoding": "base64"` key for
+///    base64-encoded contents.
+///
+/// Versions must be [`JSON_VERSION`]. Each type may only appear once, even under different keys.
+///
+/// Values under the `auto` or `*` type get their type from their contents (see
+/// [`sniff::detect`]). If it can't be told, they are copied as text, and `warn` is called with a
+/// message saying so.
+pub fn parse_json_input<R: Read>(
+    reader: R,
+    aliases: &Aliases,
+    mut warn: impl FnMut(&str),
+) -> Result<IndexMap<ContentType, Vec<u8>>> {
+    let json: serde_json::Value = serde_json::from_reader(reader)
+        .context(CliptoolsError::JsonError("cannot read JSON input".into()))?;
+    if let Some(records) = json.as_array() {
+        let mut map = IndexMap::new();
+        for (i, record) in records.iter().enumerate() {
+            let (ct, val) = parse_json_record(record, aliases, &mut warn)
+                .with_context(|| CliptoolsError::JsonError(format!("in record {}", i)))?;
+            if map.insert(ct, val).is_some() {
+                return Err(CliptoolsError::JsonError(format!(
+                    "record {}: duplicate type: {}",
+                    i, record["type"]
+                ))
+                .into());
+            }
+        }
+        return Ok(map);
+    }
+    let map = json.as_object().ok_or_else(|| {
+        CliptoolsError::JsonError("expected a JSON object or array at top level".into())
+    })?;
+    check_json_version(map)?;
+    let mut result = IndexMap::new();
+    let mut keys = Vec::new();
+    for (typ, content) in map.iter().filter(|(typ, _)| *typ != "version") {
+        let val = content.as_str().ok_or_else(|| {
+            CliptoolsError::JsonError(format!("expected a string under key {}", typ))
+        })?;
+        let ct = json_type(typ, val.as_bytes(), aliases, &mut warn)?;
+        // Different keys can resolve to the same type, e.g. `text` and `auto`.
+        if let Some((i, _, _)) = result.get_full(&ct) {
+            return Err(CliptoolsError::JsonError(format!(
+                "duplicate type: keys {} and {} are both {}",
+                keys[i],
+                typ,
+                show_ct(&ct, aliases)
+            ))
+            .into());
+        }
+        keys.push(typ);
+        result.insert(ct, val.as_bytes().to_vec());
+    }
+    Ok(result)
+}
+
+/// Checks the `version` key of a JSON object, if it has one.
+fn check_json_version(object: &serde_json::Map<String, serde_json::Value>) -> Result<()> {
+    match object.get("version") {
+        None => Ok(()),
+        Some(version) if version.as_u64() == Some(JSON_VERSION) => Ok(()),
+        Some(version) => {
+            Err(CliptoolsError::JsonError(format!("unsupported format version: {}", version))
+                .into())
+        },
+    }
+}
+
+/// Parses one record of JSON array input into its type and decoded contents.
+fn parse_json_record(
+    record: &serde_json::Value,
+    aliases: &Aliases,
+    warn: impl FnMut(&str),
+) -> Result<(ContentType, Vec<u8>)> {
+    let record =
+        record.as_object().ok_or_else(|| CliptoolsError::JsonError("expected an object".into()))?;
+    check_json_version(record)?;
+    let field = |name: &str| {
+        record.get(name).and_then(|value| value.as_str()).ok_or_else(|| {
+            CliptoolsError::JsonError(format!("expected a string under key {}", name))
+        })
+    };
+    let (typ, content) = (field("type")?, field("content")?);
+    let val = match record.get("encoding").map(|encoding| encoding.as_str()) {
+        None => content.as_bytes().to_vec(),
+        Some(Some("base64")) => encoding::decode_base64(content.as_bytes())?,
+        Some(_) => {
+            return Err(CliptoolsError::JsonError(format!(
+                "unsupported encoding: {}",
+                record["encoding"]
+            ))
+            .into())
+        },
+    };
+    let ct = json_type(typ, &val, aliases, warn)?;
+    Ok((ct, val))
+}
+
+/// Resolves a type name from JSON input, detecting the type of `val` for `auto` and `*`.
+fn json_type(
+    typ: &str,
+    val: &[u8],
+    aliases: &Aliases,
+    mut warn: impl FnMut(&str),
+) -> Result<ContentType> {
+    if typ == "auto" || typ == "*" {
+        Ok(sniff::detect(val).unwrap_or_else(|| {
+            warn(&format!("cannot tell the type under key {}, copying as text", typ));
+            ContentType::Text
+        }))
+    } else {
+        string_to_ct(typ, aliases)
+            .ok_or_else(|| CliptoolsError::ArgumentError(format!("unknown type: {}", typ)).into())
+    }
+}
@@ -0,0 +1,566 @@
+//! Minimal conversion of markup formats (HTML, RTF) to plain text, and of RTF to HTML, and
+//! sanitization of HTML.
+//!
+//! These are not full parsers: they are meant to recover readable text from what applications
+//! typically put in the clipboard, not to render documents faithfully.
+
+use std::array::IntoIter;
+
+use anyhow::Result;
+use scraper::{ElementRef, Html, Node, Selector};
+
+use crate::error::CliptoolsError;
+
+/// Strips tags from HTML, decoding character references. Contents of `script` and `style`
+/// elements are dropped, and line breaks are inserted after block elements.
+pub fn html_to_text(html: &str) -> String {
+    let mut out = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&decode_entities(&rest[..start]));
+        let tag_start = &rest[start..];
+        if tag_start.starts_with("<!--") {
+            rest = tag_start.find("-->").map_or("", |end| &tag_start[end + 3..]);
+            continue;
+        }
+        let end = match tag_start.find('>') {
+            Some(end) => end,
+            None => {
+                rest = "";
+                break;
+            },
+        };
+        let tag = tag_start[1..end].trim();
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        rest = &tag_start[end + 1..];
+
+        if !closing && (name == "script" || name == "style") {
+            let closing_tag = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&closing_tag) {
+                Some(pos) => rest[pos..].find('>').map_or("", |end| &rest[pos + end + 1..]),
+                None => "",
+            };
+        } else if name == "br" || (closing && is_block(&name)) {
+            out.push('\n');
+        }
+    }
+    out.push_str(&decode_entities(rest));
+    out
+}
+
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "div" | "li" | "tr" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "pre" | "blockquote"
+    )
+}
+
+/// Decodes HTML character references (`&amp;`, `&#39;`, `&#x27;`...). Unknown references are left
+/// untouched.
+pub fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16).ok().and_then(std::char::from_u32)
+                },
+                _ if entity.starts_with('#') => {
+                    entity[1..].parse().ok().and_then(std::char::from_u32)
+                },
+                _ => None,
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// What to extract from HTML elements matched by [`select_html`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HtmlSelection<'a> {
+    /// The text contents of the element.
+    Text,
+    /// The element's markup, including its own tag.
+    Outer,
+    /// The value of an attribute; elements without it are skipped.
+    Attr(&'a str),
+}
+
+/// Extracts data from the elements of an HTML fragment matching a CSS selector.
+pub fn select_html(html: &str, selector: &str, selection: HtmlSelection) -> Result<Vec<String>> {
+    let selector = Selector::parse(selector).map_err(|e| {
+        CliptoolsError::ArgumentError(format!("invalid CSS selector {}: {:?}", selector, e))
+    })?;
+    let fragment = Html::parse_fragment(html);
+    let selected = fragment.select(&selector);
+    Ok(match selection {
+        HtmlSelection::Text => selected.map(|e| e.text().collect::<String>()).collect(),
+        HtmlSelection::Outer => selected.map(|e| e.html()).collect(),
+        HtmlSelection::Attr(name) => {
+            selected.filter_map(|e| e.value().attr(name)).map(String::from).collect()
+        },
+    })
+}
+
+/// Elements kept by [`sanitize_html`]. Other elements are replaced by their contents.
+const SAFE_ELEMENTS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "caption",
+    "cite",
+    "code",
+    "col",
+    "colgroup",
+    "dd",
+    "del",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+
+/// Elements dropped by [`sanitize_html`] along with their contents, which are not meant to be
+/// displayed as text.
+const DROPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "iframe", "frame", "frameset", "object", "embed", "applet", "noscript",
+    "template", "svg", "math", "head", "title", "link", "meta", "base", "form", "input", "button",
+    "select", "textarea",
+];
+
+/// Elements without end tag.
+const VOID_ELEMENTS: &[&str] = &["br", "col", "hr", "img"];
+
+/// Attributes kept by [`sanitize_html`], on any of the safe elements. Others, including event
+/// handlers and inline styles, are dropped.
+const SAFE_ATTRIBUTES: &[&str] =
+    &["href", "src", "alt", "title", "colspan", "rowspan", "span", "start", "cite"];
+
+/// URL schemes allowed in attributes holding URLs. URLs without a scheme are relative, and
+/// allowed too.
+const SAFE_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Whether an attribute value is a URL with a safe scheme, or a relative URL.
+fn is_safe_url(url: &str) -> bool {
+    // Browsers ignore whitespace and control characters in schemes, e.g. `java\tscript:`.
+    let url = url.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect::<String>();
+    match url.find(|c| c == ':' || c == '/' || c == '?' || c == '#') {
+        Some(end) if url[end..].starts_with(':') => {
+            SAFE_SCHEMES.iter().any(|scheme| url[..end].eq_ignore_ascii_case(scheme))
+        },
+        _ => true,
+    }
+}
+
+/// Escapes text for inclusion in HTML, in text or double-quoted attribute values.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Removes potentially dangerous constructs from HTML, using an allowlist: only common formatting
+/// elements (see [`SAFE_ELEMENTS`]) and attributes (see [`SAFE_ATTRIBUTES`]) are kept, and links
+/// and images must use http(s) or mailto URLs. Scripts, styles, embedded content and forms are
+/// removed with their contents, and comments are dropped. Other elements are replaced by their
+/// contents.
+pub fn sanitize_html(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut out = String::new();
+    sanitize_children(fragment.root_element(), &mut out);
+    out
+}
+
+fn sanitize_children(element: ElementRef, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(&escape_html(text)),
+            Node::Element(e) if DROPPED_ELEMENTS.contains(&e.name()) => {},
+            Node::Element(e) => {
+                let child = ElementRef::wrap(child).expect("node is an element");
+                let name = e.name();
+                if !SAFE_ELEMENTS.contains(&name) {
+                    sanitize_children(child, out);
+                    continue;
+                }
+                out.push('<');
+                out.push_str(name);
+                for (attr, value) in e.attrs() {
+                    let is_url = attr == "href" || attr == "src" || attr == "cite";
+                    if SAFE_ATTRIBUTES.contains(&attr) && (!is_url || is_safe_url(value)) {
+                        out.push_str(&format!(" {}=\"{}\"", attr, escape_html(value)));
+                    }
+                }
+                out.push('>');
+                if !VOID_ELEMENTS.contains(&name) {
+                    sanitize_children(child, out);
+                    out.push_str(&format!("</{}>", name));
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// RTF destinations whose contents are not part of the document text.
+const IGNORED_DESTINATIONS: &[&str] = &[
+    "fonttbl",
+    "colortbl",
+    "stylesheet",
+    "info",
+    "pict",
+    "header",
+    "footer",
+    "listtable",
+    "listoverridetable",
+    "generator",
+    "themedata",
+    "colorschememapping",
+    "latentstyles",
+    "datastore",
+    "xmlnsttbl",
+    "rsidtbl",
+];
+
+/// What [`walk_rtf`] finds in an RTF document.
+enum RtfEvent<'a> {
+    /// A character of document text.
+    Text(char),
+    /// A control word, such as `par` or `b`, and its numeric parameter.
+    Control(&'a str, Option<i32>),
+    GroupStart,
+    GroupEnd,
+}
+
+/// Parses RTF, calling `handle` for each group, control word and character of text. Escapes are
+/// decoded, and groups that are not part of the document text (such as the font table) are
+/// skipped.
+fn walk_rtf(rtf: &str, mut handle: impl FnMut(RtfEvent)) {
+    let mut chars = rtf.chars().peekable();
+    // Whether the enclosing groups are being skipped.
+    let mut groups = Vec::new();
+    let mut skipping = false;
+    // Number of characters per \u escape used as fallback for readers without unicode support,
+    // and the number left to skip after the last \u escape.
+    let mut uc = 1;
+    let mut to_skip = 0;
+
+    let mut emit = |event: RtfEvent, skipping: bool, to_skip: &mut usize| {
+        if *to_skip > 0 && matches!(event, RtfEvent::Text(_)) {
+            *to_skip -= 1;
+        } else if !skipping {
+            handle(event);
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                emit(RtfEvent::GroupStart, skipping, &mut 0);
+                groups.push(skipping);
+                to_skip = 0;
+            },
+            '}' => {
+                skipping = groups.pop().unwrap_or(false);
+                emit(RtfEvent::GroupEnd, skipping, &mut 0);
+                to_skip = 0;
+            },
+            '\r' | '\n' => {},
+            '\\' => match chars.next() {
+                Some(c @ '\\') | Some(c @ '{') | Some(c @ '}') => {
+                    emit(RtfEvent::Text(c), skipping, &mut to_skip)
+                },
+                Some('\'') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    if let Ok(byte) = u8::from_str_radix(&hex, 16) {
+                        emit(RtfEvent::Text(char::from(byte)), skipping, &mut to_skip);
+                    }
+                },
+                Some('*') => skipping = true,
+                Some('~') => emit(RtfEvent::Text(' '), skipping, &mut to_skip),
+                Some('_') => emit(RtfEvent::Text('-'), skipping, &mut to_skip),
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let mut word = c.to_string();
+                    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphabetic()) {
+                        word.push(c);
+                        chars.next();
+                    }
+                    let mut param = String::new();
+                    if chars.peek() == Some(&'-') {
+                        param.push('-');
+                        chars.next();
+                    }
+                    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                        param.push(c);
+                        chars.next();
+                    }
+                    if chars.peek() == Some(&' ') {
+                        chars.next();
+                    }
+                    let param = param.parse::<i32>().ok();
+                    match word.as_str() {
+                        "uc" => uc = param.unwrap_or(1).max(0) as usize,
+                        "u" => {
+                            // Code points above 32767 are written as negative numbers.
+                            let code = param.unwrap_or(0);
+                            let code = if code < 0 { code + 65536 } else { code };
+                            if let Some(c) = std::char::from_u32(code as u32) {
+                                emit(RtfEvent::Text(c), skipping, &mut to_skip);
+                            }
+                            to_skip = uc;
+                        },
+                        w if IGNORED_DESTINATIONS.contains(&w) => skipping = true,
+                        w => emit(RtfEvent::Control(w, param), skipping, &mut to_skip),
+                    }
+                },
+                // Other control symbols (optional hyphens, etc.) have no text equivalent.
+                _ => {},
+            },
+            c => emit(RtfEvent::Text(c), skipping, &mut to_skip),
+        }
+    }
+}
+
+/// Strips control words and groups from RTF, keeping the document text.
+pub fn rtf_to_text(rtf: &str) -> String {
+    let mut out = String::new();
+    walk_rtf(rtf, |event| match event {
+        RtfEvent::Text(c) => out.push(c),
+        RtfEvent::Control("par", _) | RtfEvent::Control("line", _) => out.push('\n'),
+        RtfEvent::Control("tab", _) => out.push('\t'),
+        _ => {},
+    });
+    out
+}
+
+/// Character formatting that [`rtf_to_html`] keeps.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+struct RtfFormat {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl RtfFormat {
+    fn tags(self) -> impl DoubleEndedIterator<Item = &'static str> {
+        let tags = [(self.bold, "b"), (self.italic, "i"), (self.underline, "u")];
+        IntoIter::new(tags).filter(|(on, _)| *on).map(|(_, tag)| tag)
+    }
+}
+
+/// Converts RTF to basic HTML: paragraphs, line breaks, and bold, italic and underlined text.
+/// Other formatting is dropped, keeping the text.
+pub fn rtf_to_html(rtf: &str) -> String {
+    let mut out = String::new();
+    let mut groups = Vec::new();
+    // Formatting in effect in the document, and the one of the currently open HTML tags.
+    let mut format = RtfFormat::default();
+    let mut open = RtfFormat::default();
+    let mut in_paragraph = false;
+
+    let close_tags = |out: &mut String, open: &mut RtfFormat| {
+        open.tags().rev().for_each(|tag| out.push_str(&format!("</{}>", tag)));
+        *open = RtfFormat::default();
+    };
+
+    walk_rtf(rtf, |event| match event {
+        RtfEvent::GroupStart => groups.push(format),
+        RtfEvent::GroupEnd => format = groups.pop().unwrap_or_default(),
+        RtfEvent::Control("par", _) => {
+            if in_paragraph {
+                close_tags(&mut out, &mut open);
+                out.push_str("</p>\n");
+            } else {
+                out.push_str("<p></p>\n");
+            }
+            in_paragraph = false;
+        },
+        RtfEvent::Control("b", param) => format.bold = param != Some(0),
+        RtfEvent::Control("i", param) => format.italic = param != Some(0),
+        RtfEvent::Control("ul", param) => format.underline = param != Some(0),
+        RtfEvent::Control("ulnone", _) => format.underline = false,
+        RtfEvent::Control("plain", _) => format = RtfFormat::default(),
+        RtfEvent::Control(word, _) => {
+            let text = match word {
+                "line" => "<br>",
+                "tab" => "\t",
+                _ => return,
+            };
+            if !in_paragraph {
+                out.push_str("<p>");
+                in_paragraph = true;
+            }
+            out.push_str(text);
+        },
+        RtfEvent::Text(c) => {
+            if !in_paragraph {
+                out.push_str("<p>");
+                in_paragraph = true;
+            }
+            if open != format {
+                close_tags(&mut out, &mut open);
+                format.tags().for_each(|tag| out.push_str(&format!("<{}>", tag)));
+                open = format;
+            }
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                c => out.push(c),
+            }
+        },
+    });
+    if in_paragraph {
+        close_tags(&mut out, &mut open);
+        out.push_str("</p>\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decode_entities, html_to_text, rtf_to_html, rtf_to_text, sanitize_html, select_html,
+        HtmlSelection,
+    };
+
+    #[test]
+    fn html_nested_tags() {
+        let html = "<div><p>Hello <b>bold <i>world</i></b></p><p>second</p></div>";
+        assert_eq!(html_to_text(html), "Hello bold world\nsecond\n\n");
+    }
+
+    #[test]
+    fn html_script_and_comments() {
+        let html =
+            "a<script type=\"x\">if (a < b) {}</script>b<!-- <p>c</p> -->d<STYLE>p{}</style>";
+        assert_eq!(html_to_text(html), "abd");
+    }
+
+    #[test]
+    fn html_entities() {
+        assert_eq!(
+            html_to_text("a&lt;b&gt; &amp;amp; &#39;&#x41;&unknown; &"),
+            "a<b> &amp; 'A&unknown; &"
+        );
+        assert_eq!(decode_entities("&quot;x&quot;&nbsp;"), "\"x\" ");
+    }
+
+    #[test]
+    fn html_select() {
+        let html = "<p>See <a href=\"https://a\">a</a> and <a href=\"https://b\">b</a></p>";
+        assert_eq!(select_html(html, "a", HtmlSelection::Text).unwrap(), vec!["a", "b"]);
+        assert_eq!(
+            select_html(html, "a", HtmlSelection::Attr("href")).unwrap(),
+            vec!["https://a", "https://b"]
+        );
+        assert_eq!(
+            select_html(html, "a[href$=b]", HtmlSelection::Outer).unwrap(),
+            vec!["<a href=\"https://b\">b</a>"]
+        );
+        assert!(select_html(html, "p >", HtmlSelection::Text).is_err());
+    }
+
+    #[test]
+    fn html_sanitize() {
+        let html = "<p onclick=\"steal()\" class=\"x\">Hi <b>there</b><script>alert(1)</script>\
+                    <style>p{}</style></p><!-- note --><a href=\" JavaScript:alert(1)\">bad</a>\
+                    <a href=\"https://a.b/?q=1&amp;r=2\" target=\"_blank\">good</a>\
+                    <img src=\"x.png\" onerror=\"steal()\"><font color=\"red\">plain</font>\
+                    <iframe src=\"https://evil\">frame</iframe>";
+        assert_eq!(
+            sanitize_html(html),
+            "<p>Hi <b>there</b></p><a>bad</a><a href=\"https://a.b/?q=1&amp;r=2\">good</a>\
+             <img src=\"x.png\">plain"
+        );
+        assert_eq!(sanitize_html("a &lt; b<br/>c"), "a &lt; b<br>c");
+        assert_eq!(sanitize_html("<a href=\"java&#9;script:x\">x</a>"), "<a>x</a>");
+    }
+
+    #[test]
+    fn rtf_plain() {
+        let rtf = "{\\rtf1\\ansi{\\fonttbl\\f0\\fswiss Helvetica;}\\f0\\pard\n\
+                   This is {\\b bold}\\par\nsecond\\tab line}";
+        assert_eq!(rtf_to_text(rtf), "This is bold\nsecond\tline");
+    }
+
+    #[test]
+    fn rtf_escapes() {
+        let rtf = "{\\rtf1 a\\{b\\}c\\\\ caf\\'e9 \\u8364?x{\\*\\generator Foo;}}";
+        assert_eq!(rtf_to_text(rtf), "a{b}c\\ caf\u{e9} \u{20ac}x");
+    }
+
+    #[test]
+    fn rtf_html() {
+        let rtf =
+            "{\\rtf1\\ansi{\\fonttbl\\f0 Helvetica;}\\pard Hello {\\b bold \\i both}\\i0  \\ul \
+                   under\\ulnone  <x>\\par\n\\par second\\line line\\par}";
+        assert_eq!(
+            rtf_to_html(rtf),
+            "<p>Hello <b>bold </b><b><i>both</i></b> <u>under</u> &lt;x&gt;</p>\n<p></p>\n\
+             <p>second<br>line</p>\n"
+        );
+        assert_eq!(rtf_to_html("{\\rtf1 {\\b a}b\\b c\\plain d}"), "<p><b>a</b>b<b>c</b>d</p>\n");
+    }
+}
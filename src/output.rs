@@ -0,0 +1,284 @@
+use std::io::{ErrorKind, Write};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::error::CliptoolsError;
+use crate::JSON_VERSION;
+
+/// Writes clipboard contents to `out`. Contents that are not valid UTF-8 are only written if
+/// `binary_allowed` is set, and are never followed by a newline. With `detect_binary`, contents
+/// that are valid UTF-8 but [look binary](looks_binary) are treated the same way.
+pub fn show_content<W: Write>(
+    out: &mut W,
+    val: &[u8],
+    binary_allowed: bool,
+    detect_binary: bool,
+    add_newline: bool,
+) -> Result<()> {
+    let utf8 = std::str::from_utf8(val).context(CliptoolsError::Utf8Error);
+    match (utf8, binary_allowed) {
+        (Ok(s), false) if detect_binary && looks_binary(s) => {
+            Err(CliptoolsError::BinaryContent.into())
+        },
+        (Ok(s), _) => show_string(out, s, add_newline),
+        (Err(e), false) => Err(e),
+        _ => write_chunked(out, val, CHUNK_SIZE),
+    }
+}
+
+/// Size of the chunks in which [`write_chunked`] is used for binary contents.
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Writes `data` to `out` in chunks of `chunk_size` bytes, flushing after each one, so that
+/// buffered writers don't grow to the size of the data and readers get it progressively.
+pub fn write_chunked<W: Write>(out: &mut W, data: &[u8], chunk_size: usize) -> Result<()> {
+    for chunk in data.chunks(chunk_size.max(1)) {
+        out.write_all(chunk).context(CliptoolsError::InternalError)?;
+        out.flush().context(CliptoolsError::InternalError)?;
+    }
+    Ok(())
+}
+
+/// Tells whether an error was caused by writing to a closed pipe, for instance when the output is
+/// piped to `head`. The reader has everything it wanted then, so this isn't a failure.
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause.downcast_ref::<std::io::Error>().map_or(false, |e| e.kind() == ErrorKind::BrokenPipe)
+    })
+}
+
+/// Maximum proportion of control characters (other than whitespace) in text that doesn't
+/// [look binary](looks_binary).
+const MAX_CONTROL_RATIO: f64 = 0.1;
+
+/// Heuristically determines whether text is binary data that happens to be valid UTF-8, i.e. if
+/// it contains NUL characters or a high proportion of other control characters. Such contents
+/// can garble a terminal.
+pub fn looks_binary(s: &str) -> bool {
+    let mut total = 0;
+    let mut control = 0;
+    for c in s.chars() {
+        if c == '\0' {
+            return true;
+        }
+        total += 1;
+        if c.is_control() && !matches!(c, '\n' | '\r' | '\t') {
+            control += 1;
+        }
+    }
+    control as f64 > total as f64 * MAX_CONTROL_RATIO
+}
+
+/// Writes text to `out`, adding a trailing newline if `add_newline` is set and there isn't one
+/// already.
+pub fn show_string<W: Write>(out: &mut W, s: &str, add_newline: bool) -> Result<()> {
+    out.write_all(s.as_bytes()).context(CliptoolsError::InternalError)?;
+    if s.as_bytes().last() != Some(&b'\n') && add_newline {
+        out.write_all(b"\n").context(CliptoolsError::InternalError)?;
+    }
+    Ok(())
+}
+
+/// Writes clipboard contents as one line of JSON, of the form
+/// `{"version": 1, "type": ..., "content": ...}`.
+/// Contents that are not valid UTF-8 are base64-encoded, which is marked by an additional
+/// `"encoding": "base64"` key.
+pub fn show_json_line<W: Write>(out: &mut W, typ: &str, val: &[u8]) -> Result<()> {
+    let entry = match std::str::from_utf8(val) {
+        Ok(s) => serde_json::json!({ "version": JSON_VERSION, "type": typ, "content": s }),
+        Err(_) => serde_json::json!({
+            "version": JSON_VERSION,
+            "type": typ,
+            "content": base64::encode(val),
+            "encoding": "base64",
+        }),
+    };
+    // Serialize first, so that write errors reach the caller as I/O errors.
+    let line = serde_json::to_string(&entry).expect("JSON values can always be serialized");
+    writeln!(out, "{}", line).context(CliptoolsError::InternalError)
+}
+
+/// Total and largest size of a set of representations, as reported by `cliptools info`.
+#[derive(Debug, PartialEq)]
+pub struct SizeTotals<'a> {
+    pub total: usize,
+    /// Name and size of the largest representation, or `None` if there are none. Ties go to the
+    /// first one.
+    pub largest: Option<(&'a str, usize)>,
+}
+
+/// Sums the sizes of representations, given as pairs of type names and sizes.
+pub fn size_totals<'a>(sizes: &[(&'a str, usize)]) -> SizeTotals<'a> {
+    let mut largest: Option<(&str, usize)> = None;
+    for &(name, size) in sizes {
+        if largest.map_or(true, |(_, max)| size > max) {
+            largest = Some((name, size));
+        }
+    }
+    SizeTotals { total: sizes.iter().map(|(_, size)| size).sum(), largest }
+}
+
+/// Sizes of a set of representations, as printed as JSON by `copy --stats`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Stats {
+    pub version: u64,
+    /// Size of each representation, in order.
+    pub types: Vec<TypeStats>,
+    pub total: usize,
+}
+
+/// Size of one representation in [`Stats`].
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TypeStats {
+    #[serde(rename = "type")]
+    pub name: String,
+    pub size: usize,
+}
+
+impl Stats {
+    /// Builds stats from pairs of type names and sizes.
+    pub fn new(sizes: &[(&str, usize)]) -> Stats {
+        Stats {
+            version: JSON_VERSION,
+            types: sizes
+                .iter()
+                .map(|&(name, size)| TypeStats { name: name.to_string(), size })
+                .collect(),
+            total: size_totals(sizes).total,
+        }
+    }
+
+    /// Formats the stats as a single line of JSON.
+    pub fn to_json_line(&self) -> String {
+        serde_json::to_string(self).expect("stats can always be serialized")
+    }
+}
+
+/// Formats a size in bytes. With `human`, sizes of 1 KiB or more are given in KiB, MiB or GiB,
+/// with one decimal.
+pub fn format_size(bytes: usize, human: bool) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if !human || bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::{
+        format_size, is_broken_pipe, looks_binary, show_content, show_json_line, size_totals,
+        write_chunked, SizeTotals, Stats,
+    };
+
+    #[test]
+    fn binary_detection() {
+        assert!(!looks_binary(""));
+        assert!(!looks_binary("plain text\r\n\twith whitespace\n"));
+        assert!(!looks_binary("some \x1b[1mbold\x1b[0m text that goes on for a while"));
+        assert!(looks_binary("a\0b"));
+        assert!(looks_binary("\x1b\x07\x08abc"));
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut out = Vec::new();
+        show_json_line(&mut out, "text", b"two\nlines").unwrap();
+        show_json_line(&mut out, "png", b"\x89PNG").unwrap();
+        let lines = std::str::from_utf8(&out).unwrap().lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let text: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(
+            text,
+            serde_json::json!({ "version": 1, "type": "text", "content": "two\nlines" })
+        );
+        let png: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(
+            png,
+            serde_json::json!({
+                "version": 1,
+                "type": "png",
+                "content": "iVBORw==",
+                "encoding": "base64",
+            })
+        );
+    }
+
+    /// Records the size of writes between flushes.
+    #[derive(Default)]
+    struct Chunks(Vec<usize>, usize);
+
+    impl Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.1 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.0.push(self.1);
+            self.1 = 0;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn chunked() {
+        let mut out = Chunks::default();
+        write_chunked(&mut out, &[0; 10], 4).unwrap();
+        assert_eq!(out.0, vec![4, 4, 2]);
+    }
+
+    /// A writer whose reader has gone away.
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn broken_pipe() {
+        let err = show_content(&mut ClosedPipe, b"text", false, false, true).unwrap_err();
+        assert!(is_broken_pipe(&err));
+        let err = show_content(&mut ClosedPipe, b"\xff\xfe", true, false, false).unwrap_err();
+        assert!(is_broken_pipe(&err));
+        let err = show_content(&mut Vec::new(), b"\xff\xfe", false, false, false).unwrap_err();
+        assert!(!is_broken_pipe(&err));
+    }
+
+    #[test]
+    fn sizes() {
+        let totals = size_totals(&[("text", 3), ("html", 10), ("png", 10)]);
+        assert_eq!(totals, SizeTotals { total: 23, largest: Some(("html", 10)) });
+        assert_eq!(size_totals(&[]), SizeTotals { total: 0, largest: None });
+
+        assert_eq!(format_size(1023, true), "1023 bytes");
+        assert_eq!(format_size(1536, true), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024, true), "5.0 MiB");
+        assert_eq!(format_size(3 << 30, true), "3.0 GiB");
+        assert_eq!(format_size(1536, false), "1536 bytes");
+    }
+
+    #[test]
+    fn stats_json() {
+        let stats = Stats::new(&[("html", 10), ("text", 3)]);
+        assert_eq!(
+            stats.to_json_line(),
+            r#"{"version":1,"types":[{"type":"html","size":10},{"type":"text","size":3}],"total":13}"#
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! A one-shot description of the clipboard state as JSON, for bug reports.
+
+use serde_json::{json, Value};
+
+use crate::aliases::Aliases;
+use crate::backend::{backend_name, ClipboardBackend};
+use crate::types::show_ct;
+use crate::JSON_VERSION;
+
+/// Describes the platform, the clipboard backend, and each type in the clipboard with its alias,
+/// native name and size in bytes.
+///
+/// Failures don't abort the probe: a type whose data can't be read gets an `error` field instead
+/// of its size, and if the types can't be listed, the document gets a top-level `error` field.
+pub fn probe<B: ClipboardBackend>(board: &mut B, aliases: &Aliases) -> Value {
+    let mut report = json!({
+        "version": JSON_VERSION,
+        "platform": std::env::consts::OS,
+        "backend": backend_name(),
+    });
+    let names = match board.get_content_types() {
+        Ok(names) => names,
+        Err(arboard::Error::ContentNotAvailable) => Vec::new(),
+        Err(e) => {
+            report["types"] = json!([]);
+            report["error"] = json!(e.to_string());
+            return report;
+        },
+    };
+    let types = names
+        .into_iter()
+        .map(|name| {
+            let ct = board.normalize_content_type(name.clone());
+            let mut entry = json!({ "type": show_ct(&ct, aliases), "system": name });
+            match board.get_content_for_type(&ct) {
+                Ok(data) => entry["size"] = json!(data.len()),
+                Err(e) => entry["error"] = json!(e.to_string()),
+            }
+            entry
+        })
+        .collect::<Vec<_>>();
+    report["types"] = json!(types);
+    report
+}
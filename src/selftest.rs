@@ -0,0 +1,73 @@
+//! A diagnostic checking that data copied to the clipboard can be read back unchanged, for users to
+//! confirm that cliptools works with their clipboard backend.
+
+use std::array::IntoIter;
+
+use anyhow::Result;
+use arboard::ContentType;
+
+use crate::backend::ClipboardBackend;
+
+/// Text copied by the self-test, with some non-ASCII characters and line breaks.
+const TEXT: &str = "cliptools self-test\ncaf\u{e9} \u{2713}\r\n";
+
+/// A 1x1 transparent PNG image.
+const PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+/// Result of copying and reading back one format.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Passed,
+    /// The data could not be set or read back, or was changed, for the given reason.
+    Failed(String),
+    /// The backend doesn't support the format.
+    Unsupported,
+}
+
+/// Copies a known payload in each format, and checks that it reads back unchanged. Returns the
+/// outcome for each format, named by its alias.
+///
+/// The clipboard contents are restored afterwards, as far as they can be read back. An error is
+/// only returned if that fails.
+pub fn run<B: ClipboardBackend>(board: &mut B) -> Result<Vec<(&'static str, Outcome)>> {
+    let backup = crate::get_all(board).unwrap_or_default();
+    let payloads: [(&str, fn() -> ContentType, &[u8]); 2] =
+        [("text", || ContentType::Text, TEXT.as_bytes()), ("png", || ContentType::Png, PNG)];
+    let outcomes = IntoIter::new(payloads)
+        .map(|(name, ct, payload)| (name, round_trip(board, ct, payload)))
+        .collect();
+    if backup.is_empty() {
+        crate::clear(board)?;
+    } else {
+        crate::set(board, backup)?;
+    }
+    Ok(outcomes)
+}
+
+/// Copies `payload` as the content type made by `ct`, and reads it back.
+fn round_trip<B: ClipboardBackend>(
+    board: &mut B,
+    ct: fn() -> ContentType,
+    payload: &[u8],
+) -> Outcome {
+    match board.set_content_types(IntoIter::new([(ct(), payload.to_vec())]).collect()) {
+        Ok(()) => {},
+        Err(arboard::Error::ClipboardNotSupported) => return Outcome::Unsupported,
+        Err(e) => return Outcome::Failed(format!("cannot set data: {}", e)),
+    }
+    match board.get_content_for_type(&ct()) {
+        Ok(data) if data == payload => Outcome::Passed,
+        Ok(data) => Outcome::Failed(format!(
+            "read back {} bytes that differ from the {} bytes copied",
+            data.len(),
+            payload.len()
+        )),
+        Err(e) => Outcome::Failed(format!("cannot read data back: {}", e)),
+    }
+}
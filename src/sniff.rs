@@ -0,0 +1,251 @@
+//! Heuristic checks that data is in the format it is labelled with, based on its contents.
+
+use arboard::ContentType;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Skips leading whitespace and a UTF-8 byte order mark.
+fn trim_start(data: &[u8]) -> &[u8] {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    let start = data.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(data.len());
+    &data[start..]
+}
+
+/// Whether `data` looks like HTML, i.e. is text containing a tag, comment or doctype.
+pub fn looks_like_html(data: &[u8]) -> bool {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return false,
+    };
+    text.match_indices('<').any(|(i, _)| {
+        text[i + 1..]
+            .chars()
+            .next()
+            .map_or(false, |c| c.is_ascii_alphabetic() || c == '/' || c == '!')
+    })
+}
+
+/// Whether `data` looks like a list of URLs: text whose non-comment lines are URLs with a scheme
+/// and no whitespace.
+pub fn looks_like_url(data: &[u8]) -> bool {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return false,
+    };
+    let mut urls = text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#'));
+    let is_url = |line: &str| match line.split_once(':') {
+        Some((scheme, rest)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                && !rest.is_empty()
+                && !line.contains(char::is_whitespace)
+        },
+        None => false,
+    };
+    match urls.next() {
+        Some(first) => is_url(first) && urls.all(is_url),
+        None => false,
+    }
+}
+
+/// Whether `data` plausibly is in the format `ct`. Custom types can't be checked, so any data
+/// conforms to them.
+pub fn conforms_to(ct: &ContentType, data: &[u8]) -> bool {
+    match ct {
+        ContentType::Png => data.starts_with(PNG_SIGNATURE),
+        ContentType::Pdf => data.starts_with(b"%PDF-"),
+        ContentType::Rtf => trim_start(data).starts_with(b"{\\rtf"),
+        ContentType::Html => looks_like_html(data),
+        ContentType::Url => looks_like_url(data),
+        ContentType::Text => std::str::from_utf8(data).is_ok(),
+        ContentType::Custom(_) => true,
+    }
+}
+
+/// Guesses the type of `data` from its contents. Returns `None` if the data matches several formats
+/// (e.g. it is both a URL and HTML), or isn't in any known format and isn't text either.
+pub fn detect(data: &[u8]) -> Option<ContentType> {
+    let candidates = vec![
+        ContentType::Png,
+        ContentType::Pdf,
+        ContentType::Rtf,
+        ContentType::Html,
+        ContentType::Url,
+    ];
+    let mut matching = candidates.into_iter().filter(|ct| conforms_to(ct, data));
+    match (matching.next(), matching.next()) {
+        (Some(ct), None) => Some(ct),
+        (Some(_), Some(_)) => None,
+        (None, _) => std::str::from_utf8(data).ok().map(|_| ContentType::Text),
+    }
+}
+
+/// Guesses the text encoding of `data`: `ascii`, `utf-8`, `utf-16le` or `utf-16be` (if it starts
+/// with the corresponding byte order mark), or `binary` if it doesn't look like text.
+pub fn detect_encoding(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\xff\xfe") {
+        "utf-16le"
+    } else if data.starts_with(b"\xfe\xff") {
+        "utf-16be"
+    } else if data.is_ascii() {
+        "ascii"
+    } else if std::str::from_utf8(data).is_ok() {
+        "utf-8"
+    } else {
+        "binary"
+    }
+}
+
+/// CRC-32 (as used by PNG and zlib) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Checks the chunk structure of a PNG file: it must start with the PNG signature and an `IHDR`
+/// chunk, end with an `IEND` chunk, and each chunk's CRC must match. Image data isn't decoded.
+pub fn validate_png(data: &[u8]) -> Result<(), String> {
+    let mut rest = data.strip_prefix(PNG_SIGNATURE).ok_or("missing PNG signature")?;
+    let mut first = true;
+    loop {
+        if rest.len() < 12 {
+            return Err("truncated chunk".into());
+        }
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if rest.len() - 12 < len {
+            return Err("truncated chunk".into());
+        }
+        let (typ, body) = (&rest[4..8], &rest[8..8 + len]);
+        let crc = &rest[8 + len..12 + len];
+        let name = String::from_utf8_lossy(typ);
+        if crc32(&rest[4..8 + len]).to_be_bytes() != crc {
+            return Err(format!("bad CRC in {} chunk", name));
+        }
+        if first && typ != b"IHDR" {
+            return Err("first chunk is not IHDR".into());
+        }
+        first = false;
+        rest = &rest[12 + len..];
+        if typ == b"IEND" {
+            return if rest.is_empty() && body.is_empty() {
+                Ok(())
+            } else {
+                Err("data after IEND chunk".into())
+            };
+        }
+    }
+}
+
+/// Checks that `data` is well-formed for the type `ct`, returning a description of the problem if
+/// not. Returns `None` for types that can't be validated: text, JSON (custom types with `json` in
+/// their name) and PNG are supported.
+pub fn validate(ct: &ContentType, data: &[u8]) -> Option<Result<(), String>> {
+    match ct {
+        ContentType::Text => {
+            Some(std::str::from_utf8(data).map(|_| ()).map_err(|e| format!("invalid UTF-8: {}", e)))
+        },
+        ContentType::Png => Some(validate_png(data)),
+        ContentType::Custom(s) if s.to_ascii_lowercase().contains("json") => Some(
+            serde_json::from_slice::<serde_json::Value>(data)
+                .map(|_| ())
+                .map_err(|e| format!("invalid JSON: {}", e)),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arboard::ContentType;
+
+    use super::{
+        conforms_to, crc32, detect, detect_encoding, validate, validate_png, PNG_SIGNATURE,
+    };
+
+    #[test]
+    fn binary_formats() {
+        assert!(conforms_to(&ContentType::Png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!conforms_to(&ContentType::Png, b"PNG"));
+        assert!(conforms_to(&ContentType::Pdf, b"%PDF-1.7\n"));
+        assert!(!conforms_to(&ContentType::Pdf, b"\x89PNG\r\n\x1a\n"));
+        assert!(conforms_to(&ContentType::Custom("image/tiff".into()), b"anything"));
+    }
+
+    #[test]
+    fn text_formats() {
+        assert!(conforms_to(&ContentType::Html, b"Hello <b>world</b>"));
+        assert!(conforms_to(&ContentType::Html, b"<!DOCTYPE html>"));
+        assert!(!conforms_to(&ContentType::Html, b"a < b"));
+        assert!(conforms_to(&ContentType::Rtf, b"\xef\xbb\xbf\n{\\rtf1 a}"));
+        assert!(!conforms_to(&ContentType::Rtf, b"rtf"));
+        assert!(conforms_to(&ContentType::Url, b"# comment\r\nhttps://a.b/c\r\nmailto:x@y\r\n"));
+        assert!(!conforms_to(&ContentType::Url, b"https://a.b/c d"));
+        assert!(!conforms_to(&ContentType::Url, b"just text"));
+        assert!(!conforms_to(&ContentType::Url, b""));
+        assert!(conforms_to(&ContentType::Text, "caf\u{e9}".as_bytes()));
+        assert!(!conforms_to(&ContentType::Text, b"\xff"));
+    }
+
+    #[test]
+    fn detection() {
+        assert_eq!(detect(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some(ContentType::Png));
+        assert_eq!(detect(b"<p>hi</p>"), Some(ContentType::Html));
+        assert_eq!(detect(b"https://a.b/c"), Some(ContentType::Url));
+        assert_eq!(detect(b"{\\rtf1 a}"), Some(ContentType::Rtf));
+        assert_eq!(detect(b"just text"), Some(ContentType::Text));
+        assert_eq!(detect(b"https://a.b/<c>"), None);
+        assert_eq!(detect(b"\xff\xfe"), None);
+    }
+
+    #[test]
+    fn encodings() {
+        assert_eq!(detect_encoding(b"plain"), "ascii");
+        assert_eq!(detect_encoding("caf\u{e9}".as_bytes()), "utf-8");
+        assert_eq!(detect_encoding(b"\xff\xfea\0"), "utf-16le");
+        assert_eq!(detect_encoding(b"\xfe\xff\0a"), "utf-16be");
+        assert_eq!(detect_encoding(b"\x89PNG\r\n"), "binary");
+    }
+
+    fn chunk(typ: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut chunk = (body.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(typ);
+        chunk.extend_from_slice(body);
+        chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+        chunk
+    }
+
+    #[test]
+    fn png_structure() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(chunk(b"IHDR", &[0; 13]));
+        png.extend(chunk(b"IDAT", b"data"));
+        png.extend(chunk(b"IEND", b""));
+        assert_eq!(validate_png(&png), Ok(()));
+        assert_eq!(validate_png(&png[..png.len() - 1]), Err("truncated chunk".into()));
+
+        let mut corrupted = png.clone();
+        corrupted[PNG_SIGNATURE.len() + 25 + 8] ^= 1;
+        assert_eq!(validate_png(&corrupted), Err("bad CRC in IDAT chunk".into()));
+
+        let mut no_header = PNG_SIGNATURE.to_vec();
+        no_header.extend(chunk(b"IEND", b""));
+        assert_eq!(validate_png(&no_header), Err("first chunk is not IHDR".into()));
+    }
+
+    #[test]
+    fn validators() {
+        let json = ContentType::Custom("application/json".into());
+        assert_eq!(validate(&json, b"{\"a\": [1]}"), Some(Ok(())));
+        assert!(matches!(validate(&json, b"{\"a\":"), Some(Err(_))));
+        assert_eq!(validate(&ContentType::Text, "caf\u{e9}".as_bytes()), Some(Ok(())));
+        assert!(matches!(validate(&ContentType::Text, b"\xff"), Some(Err(_))));
+        assert_eq!(validate(&ContentType::Pdf, b"%PDF-"), None);
+    }
+}
@@ -0,0 +1,141 @@
+//! Conversion of tabular data between the formats spreadsheets put in the clipboard: tab-separated
+//! text, and HTML tables.
+
+use scraper::{Html, Selector};
+
+use crate::markup::escape_html;
+
+/// Parses delimited text (CSV or TSV) into rows of fields. Fields may be double-quoted, in which
+/// case they can contain delimiters, newlines, and doubled quotes. Empty lines are skipped.
+pub fn parse_delimited(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if quoted => field.push(c),
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {},
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|field| !field.is_empty()) || row.len() > 1 {
+                    rows.push(std::mem::take(&mut row));
+                } else {
+                    row.clear();
+                }
+            },
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Guesses the delimiter of CSV or TSV text: tabs if the first line has any, commas otherwise.
+pub fn guess_delimiter(text: &str) -> char {
+    if text.lines().next().map_or(false, |line| line.contains('\t')) {
+        '\t'
+    } else {
+        ','
+    }
+}
+
+/// Formats rows as tab-separated text, as spreadsheets copy it. Fields containing tabs, newlines
+/// or quotes are quoted.
+pub fn to_tsv(rows: &[Vec<String>]) -> String {
+    let quote = |field: &String| {
+        if field.contains(|c| c == '\t' || c == '\n' || c == '\r' || c == '"') {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.clone()
+        }
+    };
+    rows.iter().map(|row| row.iter().map(quote).collect::<Vec<_>>().join("\t") + "\n").collect()
+}
+
+/// Formats rows as an HTML table.
+pub fn to_html_table(rows: &[Vec<String>]) -> String {
+    let mut html = String::from("<table>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for field in row {
+            html.push_str(&format!("<td>{}</td>", escape_html(field)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+/// Extracts the rows of the first table in an HTML document, or `None` if there is no table.
+pub fn parse_html_table(html: &str) -> Option<Vec<Vec<String>>> {
+    let table_selector = Selector::parse("table").expect("valid selector");
+    let row_selector = Selector::parse("tr").expect("valid selector");
+    let cell_selector = Selector::parse("td, th").expect("valid selector");
+    let document = Html::parse_fragment(html);
+    let table = document.select(&table_selector).next()?;
+    let rows = table
+        .select(&row_selector)
+        .map(|row| {
+            row.select(&cell_selector)
+                .map(|cell| cell.text().collect::<String>().trim().to_string())
+                .collect()
+        })
+        .collect();
+    Some(rows)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{guess_delimiter, parse_delimited, parse_html_table, to_html_table, to_tsv};
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|field| field.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn delimited() {
+        assert_eq!(parse_delimited("a,b\r\n1,2\n", ','), rows(&[&["a", "b"], &["1", "2"]]));
+        assert_eq!(
+            parse_delimited("\"x, \"\"y\"\"\",\"multi\nline\"\n\n,z", ','),
+            rows(&[&["x, \"y\"", "multi\nline"], &["", "z"]])
+        );
+        assert_eq!(parse_delimited("a\tb,c", '\t'), rows(&[&["a", "b,c"]]));
+        assert_eq!(guess_delimiter("a\tb\n1,2"), '\t');
+        assert_eq!(guess_delimiter("a,b\n1\t2"), ',');
+    }
+
+    #[test]
+    fn tsv() {
+        let data = rows(&[&["a", "b"], &["tab\there", "say \"hi\""]]);
+        assert_eq!(to_tsv(&data), "a\tb\n\"tab\there\"\t\"say \"\"hi\"\"\"\n");
+        assert_eq!(parse_delimited(&to_tsv(&data), '\t'), data);
+    }
+
+    #[test]
+    fn html_tables() {
+        let data = rows(&[&["Name", "Notes"], &["a & b", "<x>"]]);
+        let html = to_html_table(&data);
+        assert_eq!(
+            html,
+            "<table>\n<tr><td>Name</td><td>Notes</td></tr>\n\
+             <tr><td>a &amp; b</td><td>&lt;x&gt;</td></tr>\n</table>\n"
+        );
+        assert_eq!(parse_html_table(&html), Some(data));
+        let sheet = "<meta charset=utf-8><table><thead><tr><th> A </th></tr></thead>\
+                     <tbody><tr><td><b>1</b></td></tr></tbody></table>";
+        assert_eq!(parse_html_table(sheet), Some(rows(&[&["A"], &["1"]])));
+        assert_eq!(parse_html_table("<p>no table</p>"), None);
+    }
+}
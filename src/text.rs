@@ -0,0 +1,365 @@
+//! Processing of text clipboard contents.
+
+use std::str::FromStr;
+
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+
+use crate::error::CliptoolsError;
+
+/// A 1-indexed, inclusive range of lines, written `N` or `N-M`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LineRange, String> {
+        let parse = |n: &str| match n.trim().parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("invalid line number: {}", n)),
+            Ok(n) => Ok(n),
+        };
+        let range = match s.split_once('-') {
+            Some((start, end)) => LineRange { start: parse(start)?, end: parse(end)? },
+            None => {
+                let line = parse(s)?;
+                LineRange { start: line, end: line }
+            },
+        };
+        if range.start > range.end {
+            return Err(format!("invalid line range: {}", s));
+        }
+        Ok(range)
+    }
+}
+
+/// Returns the lines of `text` in `range`, including their line terminators, or `None` if the
+/// text doesn't have that many lines.
+pub fn select_lines(text: &str, range: LineRange) -> Option<&str> {
+    let mut start = None;
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        if i + 1 == range.start {
+            start = Some(offset);
+        }
+        offset += line.len();
+        if i + 1 == range.end {
+            return start.map(|start| &text[start..offset]);
+        }
+    }
+    None
+}
+
+/// Returns the first `n` lines of `text`, including their line terminators.
+pub fn head_lines(text: &str, n: usize) -> &str {
+    let end = text.split_inclusive('\n').take(n).map(str::len).sum();
+    &text[..end]
+}
+
+/// Returns the last `n` lines of `text`, including their line terminators.
+pub fn tail_lines(text: &str, n: usize) -> &str {
+    let count = text.split_inclusive('\n').count();
+    let start = text.split_inclusive('\n').take(count.saturating_sub(n)).map(str::len).sum();
+    &text[start..]
+}
+
+/// Replaces `{name}` placeholders in `template` by the corresponding values. `{{` and `}}` stand
+/// for literal braces.
+pub fn fill_template(template: &str, values: &[(&str, &str)]) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(pos) = rest.find(|c| c == '{' || c == '}') {
+        out.push_str(&rest[..pos]);
+        let brace = &rest[pos..pos + 1];
+        rest = &rest[pos + 1..];
+        if rest.starts_with(brace) {
+            out.push_str(brace);
+            rest = &rest[1..];
+        } else if brace == "}" {
+            return Err(format!("unmatched `}}` in template: {}", template));
+        } else {
+            let (name, after) = rest
+                .split_once('}')
+                .ok_or_else(|| format!("unmatched `{{` in template: {}", template))?;
+            let value = values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .ok_or_else(|| format!("unknown placeholder in template: {{{}}}", name))?;
+            out.push_str(value.1);
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+/// Converts all line endings in `text` (LF or CRLF) to `ending`.
+pub fn convert_line_endings(text: &str, ending: LineEnding) -> String {
+    let lf = text.replace("\r\n", "\n");
+    match ending {
+        LineEnding::Lf => lf,
+        LineEnding::Crlf => lf.replace('\n', "\r\n"),
+    }
+}
+
+/// Compiles a regular expression for [`grep_lines`], failing with
+/// [`CliptoolsError::ArgumentError`] if it is invalid.
+pub fn parse_pattern(pattern: &str, ignore_case: bool) -> Result<Regex> {
+    RegexBuilder::new(pattern).case_insensitive(ignore_case).build().map_err(|e| {
+        CliptoolsError::ArgumentError(format!("invalid pattern {}: {}", pattern, e)).into()
+    })
+}
+
+/// Keeps the lines of `text` matching `pattern`, or not matching it with `invert`, with their line
+/// endings. Patterns are matched against lines without their line ending.
+pub fn grep_lines(text: &str, pattern: &Regex, invert: bool) -> String {
+    text.split_inclusive('\n')
+        .filter(|line| pattern.is_match(line.trim_end_matches(&['\r', '\n'][..])) != invert)
+        .collect()
+}
+
+/// Replaces tabs with spaces up to the next tab stop, tab stops being every `width` columns.
+pub fn expand_tabs(text: &str, width: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = width - column % width;
+                out.extend(std::iter::repeat(' ').take(spaces));
+                column += spaces;
+            },
+            '\n' => {
+                out.push(c);
+                column = 0;
+            },
+            c => {
+                out.push(c);
+                column += 1;
+            },
+        }
+    }
+    out
+}
+
+/// Replaces runs of two spaces or more that end on a tab stop with tabs, tab stops being every
+/// `width` columns. This is the reverse of [`expand_tabs`].
+pub fn unexpand_tabs(text: &str, width: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut column = 0;
+    // Spaces seen since the last tab stop, not written yet.
+    let mut spaces = 0;
+    for c in text.chars() {
+        match c {
+            ' ' => {
+                spaces += 1;
+                column += 1;
+                if column % width == 0 {
+                    out.push(if spaces > 1 { '\t' } else { ' ' });
+                    spaces = 0;
+                }
+            },
+            // The spaces before a tab don't move it to another tab stop.
+            '\t' => {
+                out.push(c);
+                spaces = 0;
+                column += width - column % width;
+            },
+            c => {
+                out.extend(std::iter::repeat(' ').take(spaces));
+                out.push(c);
+                spaces = 0;
+                column = if c == '\n' { 0 } else { column + 1 };
+            },
+        }
+    }
+    out.extend(std::iter::repeat(' ').take(spaces));
+    out
+}
+
+/// Returns the leading whitespace common to all lines of `text` that are not blank.
+pub fn common_indent(text: &str) -> &str {
+    let mut indents = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start().len()]);
+    let first = match indents.next() {
+        Some(indent) => indent,
+        None => return "",
+    };
+    indents.fold(first, |common, indent| {
+        let len = common.bytes().zip(indent.bytes()).take_while(|(a, b)| a == b).count();
+        &common[..len]
+    })
+}
+
+/// Removes the [common indentation](common_indent) from every line of `text`, keeping the
+/// indentation of lines relative to each other. Blank lines lose the part of their whitespace
+/// that matches the start of the common indentation.
+pub fn trim_indent(text: &str) -> String {
+    let indent = common_indent(text);
+    text.split_inclusive('\n')
+        .map(|line| {
+            let len = line
+                .chars()
+                .zip(indent.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(c, _)| c.len_utf8())
+                .sum::<usize>();
+            &line[len..]
+        })
+        .collect()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Case {
+    Lower,
+    Upper,
+}
+
+/// Converts `text` to the given case, following Unicode rules (so that e.g. `ß` is uppercased to
+/// `SS`).
+pub fn change_case(text: &str, case: Case) -> String {
+    match case {
+        Case::Lower => text.to_lowercase(),
+        Case::Upper => text.to_uppercase(),
+    }
+}
+
+/// Parses JSON text and serializes it again, either indented (`pretty`) or without any whitespace.
+pub fn reformat_json(text: &str, pretty: bool) -> Result<String> {
+    let value: serde_json::Value = serde_json::from_str(text)
+        .map_err(|e| CliptoolsError::JsonError(format!("clipboard data is not JSON: {}", e)))?;
+    let formatted =
+        if pretty { serde_json::to_string_pretty(&value) } else { serde_json::to_string(&value) };
+    Ok(formatted.expect("JSON values can always be serialized"))
+}
+
+#[cfg(test)]
+mod test {
+    use regex::Regex;
+
+    use super::{
+        change_case, common_indent, convert_line_endings, expand_tabs, fill_template, grep_lines,
+        head_lines, parse_pattern, reformat_json, select_lines, tail_lines, trim_indent,
+        unexpand_tabs, Case, LineEnding, LineRange,
+    };
+
+    #[test]
+    fn parse_range() {
+        assert_eq!("3".parse(), Ok(LineRange { start: 3, end: 3 }));
+        assert_eq!("2-5".parse(), Ok(LineRange { start: 2, end: 5 }));
+        assert!("0".parse::<LineRange>().is_err());
+        assert!("5-2".parse::<LineRange>().is_err());
+        assert!("a-2".parse::<LineRange>().is_err());
+        assert!("".parse::<LineRange>().is_err());
+    }
+
+    #[test]
+    fn lines() {
+        let text = "one\ntwo\r\nthree\nfour";
+        assert_eq!(select_lines(text, "1".parse().unwrap()), Some("one\n"));
+        assert_eq!(select_lines(text, "2-3".parse().unwrap()), Some("two\r\nthree\n"));
+        assert_eq!(select_lines(text, "4".parse().unwrap()), Some("four"));
+        assert_eq!(select_lines(text, "4-5".parse().unwrap()), None);
+        assert_eq!(select_lines("", "1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn head_and_tail() {
+        let text = "one\ntwo\r\nthree\nfour";
+        assert_eq!(head_lines(text, 2), "one\ntwo\r\n");
+        assert_eq!(head_lines(text, 0), "");
+        assert_eq!(head_lines(text, 10), text);
+        assert_eq!(tail_lines(text, 2), "three\nfour");
+        assert_eq!(tail_lines("a\nb\n", 1), "b\n");
+        assert_eq!(tail_lines(text, 0), "");
+        assert_eq!(tail_lines(text, 10), text);
+    }
+
+    #[test]
+    fn line_endings() {
+        let text = "a\r\nb\nc\rd\n";
+        assert_eq!(convert_line_endings(text, LineEnding::Lf), "a\nb\nc\rd\n");
+        assert_eq!(convert_line_endings(text, LineEnding::Crlf), "a\r\nb\r\nc\rd\r\n");
+        assert_eq!(convert_line_endings("no newline", LineEnding::Crlf), "no newline");
+    }
+
+    #[test]
+    fn grep() {
+        let text = "error: a\r\ninfo: b\nERROR: c";
+        let pattern = Regex::new("^error").unwrap();
+        assert_eq!(grep_lines(text, &pattern, false), "error: a\r\n");
+        assert_eq!(grep_lines(text, &pattern, true), "info: b\nERROR: c");
+        assert_eq!(grep_lines(text, &Regex::new("b$").unwrap(), false), "info: b\n");
+        assert_eq!(grep_lines(text, &Regex::new("nope").unwrap(), false), "");
+        let pattern = parse_pattern("^error", true).unwrap();
+        assert_eq!(grep_lines(text, &pattern, false), "error: a\r\nERROR: c");
+        assert!(parse_pattern("(", false).is_err());
+    }
+
+    #[test]
+    fn tabs() {
+        assert_eq!(expand_tabs("a\tb\n\tc", 4), "a   b\n    c");
+        assert_eq!(expand_tabs("ab\t\tc", 4), "ab      c");
+        assert_eq!(unexpand_tabs("        x", 4), "\t\tx");
+        assert_eq!(unexpand_tabs("a   b", 4), "a\tb");
+        assert_eq!(unexpand_tabs("abc d  ", 4), "abc d  ");
+        assert_eq!(unexpand_tabs("a \tb", 4), "a\tb");
+        assert_eq!(unexpand_tabs(&expand_tabs("\tx\n  \ty", 4), 4), "\tx\n\ty");
+    }
+
+    #[test]
+    fn indentation() {
+        let text = "    fn a() {\r\n        b();\r\n\n  \n    }\n";
+        assert_eq!(common_indent(text), "    ");
+        assert_eq!(trim_indent(text), "fn a() {\r\n    b();\r\n\n\n}\n");
+        assert_eq!(trim_indent("\ta\n\t\tb"), "a\n\tb");
+        assert_eq!(common_indent("\ta\n  b"), "");
+        assert_eq!(trim_indent("no indent\n  here"), "no indent\n  here");
+        assert_eq!(trim_indent("  a\n     \n  b"), "a\n   \nb");
+        assert_eq!(trim_indent("    a\n\t\t\n    b"), "a\n\t\t\nb");
+        assert_eq!(trim_indent("  a\n \t\n  b"), "a\n\t\nb");
+        assert_eq!(trim_indent(""), "");
+    }
+
+    #[test]
+    fn case() {
+        assert_eq!(change_case("Straße ΣΑΣ", Case::Upper), "STRASSE ΣΑΣ");
+        assert_eq!(change_case("ÉTÉ Σ", Case::Lower), "été σ");
+    }
+
+    #[test]
+    fn template() {
+        let values = [("content", "a{b}"), ("size", "4")];
+        assert_eq!(
+            fill_template("<pre>{content}</pre> ({size} bytes)", &values),
+            Ok("<pre>a{b}</pre> (4 bytes)".into())
+        );
+        assert_eq!(fill_template("{{content}} }}", &values), Ok("{content} }".into()));
+        assert_eq!(fill_template("plain", &values), Ok("plain".into()));
+        assert!(fill_template("{nope}", &values).is_err());
+        assert!(fill_template("{content", &values).is_err());
+        assert!(fill_template("content}", &values).is_err());
+    }
+
+    #[test]
+    fn json() {
+        let json = "{\"a\": [1, 2],\n \"b\": null}";
+        assert_eq!(reformat_json(json, false).unwrap(), "{\"a\":[1,2],\"b\":null}");
+        assert_eq!(
+            reformat_json(json, true).unwrap(),
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": null\n}"
+        );
+        assert!(reformat_json("{\"a\":", true).is_err());
+    }
+}
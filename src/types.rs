@@ -0,0 +1,300 @@
+use arboard::ContentType;
+
+use crate::aliases::Aliases;
+
+/// Converts a cliptools alias (`html`, `@public.tiff`, a user alias...) or a file extension
+/// (`.html`) to a content type. If `aliases` [are strict](Aliases::set_strict), custom types
+/// (`@public.tiff`) aren't accepted.
+pub fn string_to_ct(s: &str, aliases: &Aliases) -> Option<ContentType> {
+    if let Some(system_type) = aliases.resolve_inline(s) {
+        return Some(ContentType::Custom(system_type.into()));
+    }
+    Some(match s.to_ascii_lowercase().as_str() {
+        "url" => ContentType::Url,
+        "html" => ContentType::Html,
+        "pdf" => ContentType::Pdf,
+        "png" => ContentType::Png,
+        "rtf" => ContentType::Rtf,
+        "text" => ContentType::Text,
+        _ => {
+            if let Some(ext) = s.strip_prefix('.') {
+                return extension_to_ct(ext);
+            } else if s.starts_with('@') && !aliases.is_strict() {
+                ContentType::Custom(s.chars().skip(1).collect())
+            } else if let Some(system_type) = aliases.resolve(s) {
+                ContentType::Custom(system_type.into())
+            } else {
+                return None;
+            }
+        },
+    })
+}
+
+/// Converts a content type to the alias that [`string_to_ct`] accepts for it.
+pub fn show_ct(ct: &ContentType, aliases: &Aliases) -> String {
+    match ct {
+        ContentType::Text => "text".into(),
+        ContentType::Html => "html".into(),
+        ContentType::Pdf => "pdf".into(),
+        ContentType::Png => "png".into(),
+        ContentType::Rtf => "rtf".into(),
+        ContentType::Url => "url".into(),
+        ContentType::Custom(s) => match aliases.name_of(s) {
+            Some(name) => name.into(),
+            None => format!("@{}", s),
+        },
+    }
+}
+
+/// Normalizes the case of a system type name according to the platform's conventions, so that it
+/// matches the name applications use even if it was typed with different case:
+///  - MIME types (on Linux, and on macOS) are lowercased, except for parameter values, which may
+///    be case-sensitive (`Text/HTML;charset=UTF-8` becomes `text/html;charset=UTF-8`)
+///  - macOS system type identifiers (`public.*`, `com.apple.*`) are lowercased
+///  - other names, such as X11 targets (`UTF8_STRING`), are kept as-is, as are all names on
+///    Windows, which compares them case-insensitively already
+pub fn normalize_system_type(s: &str) -> String {
+    cfg_if::cfg_if! {
+        if #[cfg(windows)] {
+            s.to_string()
+        } else if #[cfg(target_os = "macos")] {
+            let lower = s.to_ascii_lowercase();
+            if lower.starts_with("public.") || lower.starts_with("com.apple.") {
+                lower
+            } else {
+                normalize_mime(s)
+            }
+        } else {
+            normalize_mime(s)
+        }
+    }
+}
+
+/// Lowercases the type and subtype of a MIME type, keeping its parameters. Names that are not MIME
+/// types are returned unchanged.
+#[cfg(not(windows))]
+fn normalize_mime(s: &str) -> String {
+    let (essence, params) = s.split_at(s.find(';').unwrap_or(s.len()));
+    if essence.contains('/') {
+        let params = params.split(';').map(|param| match param.split_once('=') {
+            Some((name, value)) => format!("{}={}", name.to_ascii_lowercase(), value),
+            None => param.to_string(),
+        });
+        essence.to_ascii_lowercase() + &params.collect::<Vec<_>>().join(";")
+    } else {
+        s.to_string()
+    }
+}
+
+/// Returns whether data of the given type is text, which text processing options can apply to.
+/// Custom types are considered textual if their name suggests so (e.g. `text/plain`,
+/// `public.utf8-plain-text`, `UTF8_STRING`).
+pub fn is_textual(ct: &ContentType) -> bool {
+    match ct {
+        ContentType::Text | ContentType::Html | ContentType::Rtf | ContentType::Url => true,
+        ContentType::Pdf | ContentType::Png => false,
+        ContentType::Custom(s) => {
+            let s = s.to_ascii_lowercase();
+            ["text", "html", "utf8", "utf-8", "string", "json", "xml", "url"]
+                .iter()
+                .any(|hint| s.contains(hint))
+        },
+    }
+}
+
+/// Returns the usual file extension for files containing data of the given type.
+pub fn ct_to_extension(ct: &ContentType) -> Option<&'static str> {
+    match ct {
+        ContentType::Text => Some("txt"),
+        ContentType::Html => Some("html"),
+        ContentType::Pdf => Some("pdf"),
+        ContentType::Png => Some("png"),
+        ContentType::Rtf => Some("rtf"),
+        ContentType::Url | ContentType::Custom(_) => None,
+    }
+}
+
+/// Returns the content type for files with the given extension (without the leading dot).
+pub fn extension_to_ct(ext: &str) -> Option<ContentType> {
+    match ext.to_ascii_lowercase().as_str() {
+        "txt" | "text" => Some(ContentType::Text),
+        "html" | "htm" => Some(ContentType::Html),
+        "pdf" => Some(ContentType::Pdf),
+        "png" => Some(ContentType::Png),
+        "rtf" => Some(ContentType::Rtf),
+        _ => None,
+    }
+}
+
+/// Broad kind of data, used to group content types for display.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Text,
+    Image,
+    Document,
+    Web,
+    Custom,
+}
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Text => "text",
+            Category::Image => "image",
+            Category::Document => "document",
+            Category::Web => "web",
+            Category::Custom => "custom",
+        }
+    }
+}
+
+/// Returns the category of a content type. Custom types are categorized by their MIME type or
+/// macOS type identifier when it is recognizable (e.g. `image/tiff` or `public.tiff`).
+pub fn category(ct: &ContentType) -> Category {
+    match ct {
+        ContentType::Text => Category::Text,
+        ContentType::Png => Category::Image,
+        ContentType::Pdf | ContentType::Rtf => Category::Document,
+        ContentType::Html | ContentType::Url => Category::Web,
+        ContentType::Custom(s) => {
+            let s = s.to_ascii_lowercase();
+            let (major, minor) = s.split_once('/').unwrap_or(("", &s));
+            let has = |hints: &[&str]| hints.iter().any(|hint| minor.contains(hint));
+            if major == "image" || has(&["png", "tiff", "jpeg", "gif", "bmp", "image"]) {
+                Category::Image
+            } else if has(&["html", "url", "uri-list"]) {
+                Category::Web
+            } else if has(&["pdf", "rtf", "msword", "opendocument", "officedocument"]) {
+                Category::Document
+            } else if major == "text" || has(&["text", "string", "utf8"]) {
+                Category::Text
+            } else {
+                Category::Custom
+            }
+        },
+    }
+}
+
+/// Copies a content type. `ContentType` isn't `Clone`, but the same types sometimes need to be
+/// passed to the clipboard more than once.
+pub(crate) fn duplicate_ct(ct: &ContentType) -> ContentType {
+    match ct {
+        ContentType::Text => ContentType::Text,
+        ContentType::Html => ContentType::Html,
+        ContentType::Pdf => ContentType::Pdf,
+        ContentType::Png => ContentType::Png,
+        ContentType::Rtf => ContentType::Rtf,
+        ContentType::Url => ContentType::Url,
+        ContentType::Custom(s) => ContentType::Custom(s.clone()),
+    }
+}
+
+/// Whether a type name matches a filter pattern. Patterns containing `*` or `?` are globs matching
+/// the whole name; other patterns match any part of it. Matching ignores ASCII case.
+pub(crate) fn type_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+    if !pattern.contains(|c| c == '*' || c == '?') {
+        return name.contains(&pattern);
+    }
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern, and of the name where it started matching.
+    let mut star = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last `*` match one more character.
+            star = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use arboard::ContentType;
+
+    #[cfg(not(windows))]
+    use super::normalize_system_type;
+    use super::{
+        category, ct_to_extension, extension_to_ct, is_textual, string_to_ct, type_matches,
+        Category,
+    };
+    use crate::aliases::Aliases;
+
+    #[test]
+    fn extensions() {
+        let aliases = Aliases::default();
+        assert_eq!(string_to_ct(".png", &aliases), Some(ContentType::Png));
+        assert_eq!(string_to_ct(".HTM", &aliases), Some(ContentType::Html));
+        assert_eq!(string_to_ct(".exe", &aliases), None);
+        for ct in &[ContentType::Text, ContentType::Html, ContentType::Pdf, ContentType::Png] {
+            assert_eq!(extension_to_ct(ct_to_extension(ct).unwrap()).as_ref(), Some(ct));
+        }
+    }
+
+    #[test]
+    fn textual() {
+        assert!(is_textual(&ContentType::Html));
+        assert!(!is_textual(&ContentType::Png));
+        assert!(is_textual(&ContentType::Custom("public.utf8-plain-text".into())));
+        assert!(is_textual(&ContentType::Custom("application/json".into())));
+        assert!(!is_textual(&ContentType::Custom("image/tiff".into())));
+    }
+
+    #[test]
+    fn filter() {
+        assert!(type_matches("image/*", "image/png"));
+        assert!(type_matches("IMAGE/*", "image/tiff"));
+        assert!(!type_matches("image/*", "text/image/png"));
+        assert!(type_matches("png", "@image/png"));
+        assert!(type_matches("*.t?ff", "public.tiff"));
+        assert!(type_matches("*", ""));
+        assert!(type_matches("a*b*c", "aXbYbZc"));
+        assert!(!type_matches("a*b*c", "aXbYbZ"));
+    }
+
+    #[test]
+    fn inline_aliases() {
+        let mut aliases = Aliases::default();
+        aliases.add_inline("shot=image/png").unwrap();
+        aliases.add_inline("HTML=text/x-html").unwrap();
+        assert_eq!(string_to_ct("shot", &aliases), Some(ContentType::Custom("image/png".into())));
+        assert_eq!(string_to_ct("html", &aliases), Some(ContentType::Custom("text/x-html".into())));
+        assert!(aliases.add_inline("shot").is_err());
+        assert!(aliases.add_inline("=image/png").is_err());
+    }
+
+    #[test]
+    fn categories() {
+        let custom = |s: &str| category(&ContentType::Custom(s.into()));
+        assert_eq!(category(&ContentType::Png), Category::Image);
+        assert_eq!(category(&ContentType::Url), Category::Web);
+        assert_eq!(custom("image/x-unknown"), Category::Image);
+        assert_eq!(custom("public.tiff"), Category::Image);
+        assert_eq!(custom("text/html"), Category::Web);
+        assert_eq!(custom("text/plain;charset=utf-8"), Category::Text);
+        assert_eq!(custom("UTF8_STRING"), Category::Text);
+        assert_eq!(custom("com.adobe.pdf"), Category::Document);
+        assert_eq!(custom("application/x-custom"), Category::Custom);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn system_type_case() {
+        assert_eq!(normalize_system_type("Text/HTML"), "text/html");
+        assert_eq!(normalize_system_type("Text/Plain;Charset=UTF-8"), "text/plain;charset=UTF-8");
+        assert_eq!(normalize_system_type("UTF8_STRING"), "UTF8_STRING");
+    }
+}
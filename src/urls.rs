@@ -0,0 +1,123 @@
+//! Handling of the `url` content type.
+
+use anyhow::Result;
+use url::Url;
+
+use crate::error::CliptoolsError;
+
+/// Percent-encodes bytes that may not appear in a URI. Reserved characters are kept as-is, since
+/// they are meaningful in URLs, as are existing percent-encoded sequences. A single trailing line
+/// ending, as added by `echo`, is dropped rather than encoded.
+pub fn percent_encode(url: &[u8]) -> Vec<u8> {
+    let url = match url.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => url,
+    };
+    let mut out = Vec::with_capacity(url.len());
+    for (i, &b) in url.iter().enumerate() {
+        let keep = b.is_ascii_alphanumeric()
+            || b"-._~:/?#[]@!$&'()*+,;=".contains(&b)
+            || (b == b'%' && is_percent_sequence(&url[i..]));
+        if keep {
+            out.push(b);
+        } else {
+            out.extend_from_slice(format!("%{:02X}", b).as_bytes());
+        }
+    }
+    out
+}
+
+fn is_percent_sequence(s: &[u8]) -> bool {
+    s.len() >= 3 && s[0] == b'%' && s[1].is_ascii_hexdigit() && s[2].is_ascii_hexdigit()
+}
+
+/// Decodes percent-encoded sequences in a URL.
+pub fn percent_decode(url: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(url.len());
+    let mut i = 0;
+    while i < url.len() {
+        if url[i] != b'%' {
+            out.push(url[i]);
+            i += 1;
+        } else if is_percent_sequence(&url[i..]) {
+            let hex = std::str::from_utf8(&url[i + 1..i + 3]).expect("hex digits are ASCII");
+            out.push(u8::from_str_radix(hex, 16).expect("checked hex digits"));
+            i += 3;
+        } else {
+            return Err(CliptoolsError::UrlEncodingError(format!(
+                "malformed percent-encoded sequence at byte {}",
+                i
+            ))
+            .into());
+        }
+    }
+    Ok(out)
+}
+
+/// Canonicalizes a URL: the scheme and host are lowercased, default ports are removed, and `.` and
+/// `..` path segments are resolved.
+pub fn normalize_url(url: &str) -> Result<String> {
+    Url::parse(url)
+        .map(String::from)
+        .map_err(|e| CliptoolsError::UrlError(format!("{}: {}", url, e)).into())
+}
+
+/// Normalizes each URL in a `text/uri-list`. Blank lines and comments (starting with `#`) are kept
+/// as-is.
+pub fn normalize_uri_list(list: &str) -> Result<String> {
+    list.split('\n')
+        .map(|line| {
+            let url = line.trim_end_matches('\r');
+            if url.trim().is_empty() || url.starts_with('#') {
+                Ok(line.to_string())
+            } else {
+                Ok(normalize_url(url.trim())? + &line[url.len()..])
+            }
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|lines| lines.join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize_uri_list, normalize_url, percent_decode, percent_encode};
+
+    #[test]
+    fn encode() {
+        assert_eq!(
+            percent_encode(b"https://a.b/c d?q=\xc3\xa9#x"),
+            b"https://a.b/c%20d?q=%C3%A9#x"
+        );
+        assert_eq!(percent_encode(b"/already%20encoded/100%"), b"/already%20encoded/100%25");
+        assert_eq!(percent_encode(b"https://x/a b\n"), b"https://x/a%20b");
+        assert_eq!(percent_encode(b"https://x/a b\r\n"), b"https://x/a%20b");
+        assert_eq!(percent_encode(b"a\n\n"), b"a%0A");
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode(b"/c%20d?q=%c3%A9").unwrap(), "/c d?q=\u{e9}".as_bytes());
+        assert!(percent_decode(b"/100%").is_err());
+        assert!(percent_decode(b"/%zz").is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let url = "https://example.com/path with spaces/\u{1f600}?a=b&c=d";
+        assert_eq!(percent_decode(&percent_encode(url.as_bytes())).unwrap(), url.as_bytes());
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(
+            normalize_url("HTTP://Example.COM:80/a/./b/../c?q=1").unwrap(),
+            "http://example.com/a/c?q=1"
+        );
+        assert_eq!(normalize_url("https://a.b:8443").unwrap(), "https://a.b:8443/");
+        assert!(normalize_url("not a url").is_err());
+        assert_eq!(
+            normalize_uri_list("# comment\r\nHTTPS://A.B:443/x/..\r\n").unwrap(),
+            "# comment\r\nhttps://a.b/\r\n"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Waiting for the contents of the clipboard to change.
+//!
+//! Clipboards don't offer a portable change notification mechanism, so this polls the clipboard
+//! and compares its contents with the last value seen.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use arboard::ContentType;
+use chrono::{DateTime, Utc};
+
+use crate::backend::ClipboardBackend;
+use crate::error::CliptoolsError;
+use crate::hash;
+
+/// Interval between two reads of the clipboard.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Reads the given type from the clipboard, or text if `ct` is `None`. Missing data is not an
+/// error, so that copying data of the watched type counts as a change.
+fn current<B: ClipboardBackend>(board: &mut B, ct: Option<&ContentType>) -> Option<Vec<u8>> {
+    match ct {
+        Some(ct) => board.get_content_for_type(ct).ok(),
+        None => board.get_text().ok().map(String::into_bytes),
+    }
+}
+
+/// Polls the clipboard until the data of the given type differs from `last`, and returns the new
+/// data. Fails with [`CliptoolsError::Timeout`] if `deadline` passes first.
+fn next_change<B: ClipboardBackend>(
+    board: &mut B,
+    ct: Option<&ContentType>,
+    last: Option<&Vec<u8>>,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>> {
+    loop {
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return Err(CliptoolsError::Timeout.into());
+        }
+        thread::sleep(POLL_INTERVAL);
+        match current(board, ct) {
+            Some(val) if Some(&val) != last => return Ok(val),
+            _ => {},
+        }
+    }
+}
+
+/// Blocks until the data of the given type (or text if `ct` is `None`) differs from what it is when
+/// this function is called, and returns the new data. Fails with [`CliptoolsError::Timeout`] if
+/// `timeout` elapses first.
+pub fn wait_for_change<B: ClipboardBackend>(
+    board: &mut B,
+    ct: Option<&ContentType>,
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let initial = current(board, ct);
+    next_change(board, ct, initial.as_ref(), deadline)
+}
+
+/// Calls `on_change` with the new data each time the data of the given type (or text if `ct` is
+/// `None`) changes. This only returns if `on_change` fails, or once `timeout` has elapsed: then,
+/// it fails with [`CliptoolsError::Timeout`] if there was no change.
+///
+/// If `since` is given, it is the hex digest of the last data seen (see [`hash::hex_digest`]), for
+/// instance by a previous watcher: the current data counts as a change unless it has that digest.
+pub fn watch<B, F>(
+    board: &mut B,
+    ct: Option<&ContentType>,
+    timeout: Option<Duration>,
+    since: Option<&str>,
+    mut on_change: F,
+) -> Result<()>
+where
+    B: ClipboardBackend,
+    F: FnMut(&[u8]) -> Result<()>,
+{
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut last = current(board, ct);
+    if let Some(since) = since {
+        last = last.filter(|val| hash::digest_matches(since, val));
+    }
+    let mut changed = false;
+    loop {
+        let val = match next_change(board, ct, last.as_ref(), deadline) {
+            Ok(val) => val,
+            // The deadline passed.
+            Err(_) if changed => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        on_change(&val)?;
+        last = Some(val);
+        changed = true;
+    }
+}
+
+/// Parses an RFC 3339 timestamp, such as `2021-06-01T18:00:00+02:00`, and returns the time left
+/// until then. Fails with [`CliptoolsError::ArgumentError`] if the timestamp is invalid or not in
+/// the future.
+pub fn parse_deadline(s: &str) -> Result<Duration> {
+    let deadline = DateTime::parse_from_rfc3339(s).map_err(|e| {
+        CliptoolsError::ArgumentError(format!("invalid RFC 3339 timestamp {}: {}", s, e))
+    })?;
+    (deadline.with_timezone(&Utc) - Utc::now())
+        .to_std()
+        .ok()
+        .filter(|left| *left > Duration::from_secs(0))
+        .ok_or_else(|| CliptoolsError::ArgumentError(format!("deadline {} has passed", s)).into())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use chrono::{Duration as ChronoDuration, Utc};
+
+    use super::parse_deadline;
+
+    #[test]
+    fn deadlines() {
+        let in_an_hour = (Utc::now() + ChronoDuration::hours(1)).to_rfc3339();
+        let left = parse_deadline(&in_an_hour).unwrap();
+        assert!(left > Duration::from_secs(3500) && left <= Duration::from_secs(3600));
+        assert!(parse_deadline("2020-01-01T00:00:00Z").is_err());
+        assert!(parse_deadline("2999-01-01 00:00").is_err());
+    }
+}
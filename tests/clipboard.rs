@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+
+use arboard::ContentType;
+use cliptools::aliases::Aliases;
+use cliptools::selftest::Outcome;
+use cliptools::{ClipboardBackend, CliptoolsError, Selection};
+use indexmap::IndexMap;
+
+/// An in-memory clipboard, storing contents under native type names.
+#[derive(Default)]
+struct FakeClipboard {
+    contents: Vec<(String, Vec<u8>)>,
+    /// The contents of the selection that isn't selected, if the fake has a primary selection.
+    unselected: Option<Vec<(String, Vec<u8>)>>,
+    primary_selected: bool,
+}
+
+fn contents_of(contents: &[(&str, &[u8])]) -> Vec<(String, Vec<u8>)> {
+    contents.iter().map(|(t, v)| (t.to_string(), v.to_vec())).collect()
+}
+
+impl FakeClipboard {
+    fn with(contents: &[(&str, &[u8])]) -> FakeClipboard {
+        FakeClipboard { contents: contents_of(contents), ..FakeClipboard::default() }
+    }
+
+    /// A fake with a primary selection, like X11's.
+    fn with_primary(contents: &[(&str, &[u8])], primary: &[(&str, &[u8])]) -> FakeClipboard {
+        FakeClipboard {
+            contents: contents_of(contents),
+            unselected: Some(contents_of(primary)),
+            primary_selected: false,
+        }
+    }
+}
+
+fn system_name(ct: &ContentType) -> String {
+    match ct {
+        ContentType::Text => "text/plain".into(),
+        ContentType::Html => "text/html".into(),
+        ContentType::Pdf => "application/pdf".into(),
+        ContentType::Png => "image/png".into(),
+        ContentType::Rtf => "text/rtf".into(),
+        ContentType::Url => "text/uri-list".into(),
+        ContentType::Custom(s) => s.clone(),
+    }
+}
+
+impl ClipboardBackend for FakeClipboard {
+    fn get_text(&mut self) -> Result<String, arboard::Error> {
+        let val = self.get_content_for_type(&ContentType::Text)?;
+        String::from_utf8(val).map_err(|_| arboard::Error::ConversionFailure)
+    }
+
+    fn get_content_for_type(&mut self, ct: &ContentType) -> Result<Vec<u8>, arboard::Error> {
+        let name = system_name(ct);
+        self.contents
+            .iter()
+            .find(|(t, _)| *t == name)
+            .map(|(_, v)| v.clone())
+            .ok_or(arboard::Error::ContentNotAvailable)
+    }
+
+    fn get_content_types(&mut self) -> Result<Vec<String>, arboard::Error> {
+        // Like some native backends, report an empty clipboard as an error.
+        if self.contents.is_empty() {
+            return Err(arboard::Error::ContentNotAvailable);
+        }
+        Ok(self.contents.iter().map(|(t, _)| t.clone()).collect())
+    }
+
+    fn set_content_types(
+        &mut self,
+        map: HashMap<ContentType, Vec<u8>>,
+    ) -> Result<(), arboard::Error> {
+        self.contents = map.into_iter().map(|(ct, v)| (system_name(&ct), v)).collect();
+        Ok(())
+    }
+
+    fn normalize_content_type(&mut self, s: String) -> ContentType {
+        match s.as_str() {
+            "text/plain" | "UTF8_STRING" => ContentType::Text,
+            "text/html" => ContentType::Html,
+            "application/pdf" => ContentType::Pdf,
+            "image/png" => ContentType::Png,
+            "text/rtf" => ContentType::Rtf,
+            "text/uri-list" => ContentType::Url,
+            _ => ContentType::Custom(s),
+        }
+    }
+
+    fn select(&mut self, selection: Selection) -> Result<(), arboard::Error> {
+        let primary = match selection {
+            Selection::Clipboard => false,
+            Selection::Primary if self.unselected.is_some() => true,
+            _ => return Err(arboard::Error::ClipboardNotSupported),
+        };
+        if primary != self.primary_selected {
+            std::mem::swap(&mut self.contents, self.unselected.as_mut().unwrap());
+            self.primary_selected = primary;
+        }
+        Ok(())
+    }
+}
+
+fn error_of(err: anyhow::Error) -> CliptoolsError {
+    match err.downcast::<CliptoolsError>() {
+        Ok(e) => e,
+        Err(e) => panic!("unexpected error type: {}", e),
+    }
+}
+
+#[test]
+fn paste_text() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"hello")]);
+    assert_eq!(cliptools::get_text(&mut board).unwrap(), "hello");
+}
+
+#[test]
+fn paste_text_fallback() {
+    let mut board = FakeClipboard::with(&[("text/html", b"<p>a &amp; b</p>")]);
+    assert!(cliptools::get_text(&mut board).is_err());
+    assert_eq!(cliptools::get_text_with_fallback(&mut board).unwrap(), "a & b\n");
+
+    let mut board = FakeClipboard::with(&[("text/rtf", b"{\\rtf1 from rtf}")]);
+    assert_eq!(cliptools::get_text_with_fallback(&mut board).unwrap(), "from rtf");
+
+    let mut board = FakeClipboard::with(&[("image/png", b"")]);
+    let err = cliptools::get_text_with_fallback(&mut board).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::DataNotFound));
+}
+
+#[test]
+fn paste_missing_type() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"hello")]);
+    let err = cliptools::get(&mut board, &ContentType::Html).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::DataNotFound));
+}
+
+#[test]
+fn list_aliases_sorted_and_deduped() {
+    let mut board = FakeClipboard::with(&[
+        ("UTF8_STRING", b"a"),
+        ("text/html", b"<b>a</b>"),
+        ("text/plain", b"a"),
+        ("image/tiff", b""),
+    ]);
+    let types = cliptools::list(&mut board, &Aliases::default(), false, None, true).unwrap();
+    assert_eq!(types, vec!["@image/tiff", "html", "text"]);
+    let types = cliptools::list(&mut board, &Aliases::default(), true, None, true).unwrap();
+    assert_eq!(types, vec!["UTF8_STRING", "text/html", "text/plain", "image/tiff"]);
+    let types = cliptools::list(&mut board, &Aliases::default(), false, None, false).unwrap();
+    assert_eq!(types, vec!["text", "html", "text", "@image/tiff"]);
+    assert_eq!(cliptools::count_types(&mut board, &Aliases::default()).unwrap(), 3);
+}
+
+#[test]
+fn list_empty() {
+    let mut board = FakeClipboard::default();
+    assert!(cliptools::list(&mut board, &Aliases::default(), false, None, true)
+        .unwrap()
+        .is_empty());
+    assert!(cliptools::list(&mut board, &Aliases::default(), true, None, true).unwrap().is_empty());
+    assert!(cliptools::get_all(&mut board).unwrap().is_empty());
+    assert_eq!(cliptools::count_types(&mut board, &Aliases::default()).unwrap(), 0);
+}
+
+#[test]
+fn list_filtered() {
+    let mut board = FakeClipboard::with(&[
+        ("UTF8_STRING", b"a"),
+        ("text/plain", b"a"),
+        ("image/png", b""),
+        ("image/tiff", b""),
+    ]);
+    let aliases = Aliases::default();
+    let types = cliptools::list(&mut board, &aliases, true, Some("image/*"), true).unwrap();
+    assert_eq!(types, vec!["image/png", "image/tiff"]);
+    let types = cliptools::list(&mut board, &aliases, false, Some("t"), true).unwrap();
+    assert_eq!(types, vec!["@image/tiff", "text"]);
+    let types = cliptools::list(&mut board, &aliases, false, Some("png"), true).unwrap();
+    assert_eq!(types, vec!["png"]);
+}
+
+#[test]
+fn required_types() {
+    let mut board = FakeClipboard::with(&[("UTF8_STRING", b"a"), ("text/html", b"<b>a</b>")]);
+    let required = [ContentType::Text, ContentType::Html];
+    assert!(cliptools::missing_types(&mut board, &required).unwrap().is_empty());
+    let required = [ContentType::Html, ContentType::Png, ContentType::Rtf];
+    let missing = cliptools::missing_types(&mut board, &required).unwrap();
+    assert_eq!(missing, vec![&ContentType::Png, &ContentType::Rtf]);
+}
+
+#[test]
+fn copy_json() {
+    let mut board = FakeClipboard::default();
+    let input = br#"{"version": 1, "html": "<b>a</b>", "text": "a", "@text/x-custom": "c"}"#;
+    let map = cliptools::parse_json_input(&input[..], &Aliases::default(), |_| ()).unwrap();
+    cliptools::set(&mut board, map.into_iter().collect()).unwrap();
+    assert_eq!(cliptools::get(&mut board, &ContentType::Html).unwrap(), b"<b>a</b>");
+    assert_eq!(cliptools::get_text(&mut board).unwrap(), "a");
+    let custom = ContentType::Custom("text/x-custom".into());
+    assert_eq!(cliptools::get(&mut board, &custom).unwrap(), b"c");
+}
+
+#[test]
+fn strict_types() {
+    let mut aliases = Aliases::default();
+    aliases.add_inline("shot=image/png").unwrap();
+    aliases.set_strict(true);
+    let input = br#"{"text": "a", "shot": ""}"#;
+    assert_eq!(cliptools::parse_json_input(&input[..], &aliases, |_| ()).unwrap().len(), 2);
+    let input = br#"{"text": "a", "@x/y": ""}"#;
+    let err = cliptools::parse_json_input(&input[..], &aliases, |_| ()).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::ArgumentError(_)));
+    let err = cliptools::framed::parse_framed(b"@x/y\n1\na\n", &aliases).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::ProtocolError(_)));
+}
+
+#[test]
+fn copy_json_auto() {
+    let input = br#"{"auto": "<b>a</b>", "*": "https://a.b/<c>"}"#;
+    let mut warnings = Vec::new();
+    let map = cliptools::parse_json_input(&input[..], &Aliases::default(), |msg| {
+        warnings.push(msg.to_string())
+    })
+    .unwrap();
+    assert_eq!(map.get(&ContentType::Html).unwrap(), b"<b>a</b>");
+    assert_eq!(map.get(&ContentType::Text).unwrap(), b"https://a.b/<c>");
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn copy_json_order() {
+    let input = br#"{"text": "a", "@x/y": "", "version": 1, "html": "<b>a</b>"}"#;
+    let aliases = Aliases::default();
+    let map = cliptools::parse_json_input(&input[..], &aliases, |_| ()).unwrap();
+    assert_eq!(
+        cliptools::summarize(&map, &aliases),
+        vec!["text: 1 bytes", "@x/y: 0 bytes", "html: 8 bytes"]
+    );
+}
+
+#[test]
+fn copy_json_records() {
+    let input = br#"[
+        {"version": 1, "type": "html", "content": "<b>a</b>"},
+        {"type": "png", "content": "iVBORw==", "encoding": "base64"},
+        {"type": "text", "content": "a"}
+    ]"#;
+    let aliases = Aliases::default();
+    let map = cliptools::parse_json_input(&input[..], &aliases, |_| ()).unwrap();
+    assert_eq!(
+        cliptools::summarize(&map, &aliases),
+        vec!["html: 8 bytes", "png: 4 bytes", "text: 1 bytes"]
+    );
+    assert_eq!(map[&ContentType::Png], b"\x89PNG");
+
+    let invalid: [&[u8]; 5] = [
+        br#"[{"type": "text"}]"#,
+        br#"[{"type": "text", "content": "a"}, 1]"#,
+        br#"[{"type": "text", "content": "a"}, {"type": "text", "content": "b"}]"#,
+        br#"[{"type": "png", "content": "a", "encoding": "hex"}]"#,
+        br#"[{"type": "text", "content": "a", "version": 2}]"#,
+    ];
+    for input in &invalid {
+        let err = cliptools::parse_json_input(*input, &aliases, |_| ()).unwrap_err();
+        assert!(matches!(error_of(err), CliptoolsError::JsonError(_)));
+    }
+}
+
+#[test]
+fn copy_json_errors() {
+    let aliases = Aliases::default();
+    let err = cliptools::parse_json_input(&b"\"a\""[..], &aliases, |_| ()).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::JsonError(_)));
+    let err = cliptools::parse_json_input(&br#"{"text": 1}"#[..], &aliases, |_| ()).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::JsonError(_)));
+    let err = cliptools::parse_json_input(&br#"{"version": 2}"#[..], &aliases, |_| ()).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::JsonError(_)));
+    let err = cliptools::parse_json_input(&br#"{"nope": "a"}"#[..], &aliases, |_| ()).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::ArgumentError(_)));
+    let input = br#"{"text": "a", "auto": "b"}"#;
+    let err = cliptools::parse_json_input(&input[..], &aliases, |_| ()).unwrap_err();
+    match error_of(err) {
+        CliptoolsError::JsonError(message) => assert!(message.contains("text and auto")),
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn binary_guard() {
+    let binary = [0xff, 0xfe, 0x00];
+    let mut out = Vec::new();
+    let err = cliptools::show_content(&mut out, &binary, false, false, true).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::Utf8Error));
+    assert!(out.is_empty());
+
+    cliptools::show_content(&mut out, &binary, true, false, true).unwrap();
+    assert_eq!(out, binary);
+
+    let mut out = Vec::new();
+    cliptools::show_content(&mut out, b"text", false, false, true).unwrap();
+    assert_eq!(out, b"text\n");
+
+    let err = cliptools::show_content(&mut out, b"\0\x07", false, true, true).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::BinaryContent));
+    cliptools::show_content(&mut out, b"\0\x07", true, true, true).unwrap();
+}
+
+#[test]
+fn copy_merge() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"old"), ("text/html", b"<b>old</b>")]);
+    let mut map = IndexMap::new();
+    map.insert(ContentType::Text, b"new".to_vec());
+    cliptools::merge_existing(&mut board, &mut map).unwrap();
+    cliptools::set(&mut board, map.into_iter().collect()).unwrap();
+    assert_eq!(cliptools::get_text(&mut board).unwrap(), "new");
+    assert_eq!(cliptools::get(&mut board, &ContentType::Html).unwrap(), b"<b>old</b>");
+}
+
+#[test]
+fn remove_type() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"a"), ("text/html", b"<b>a</b>")]);
+    cliptools::remove_type(&mut board, &ContentType::Html).unwrap();
+    assert_eq!(
+        cliptools::list(&mut board, &Aliases::default(), true, None, true).unwrap(),
+        vec!["text/plain"]
+    );
+    let err = cliptools::remove_type(&mut board, &ContentType::Html).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::DataNotFound));
+    cliptools::remove_type(&mut board, &ContentType::Text).unwrap();
+    assert_eq!(cliptools::count_types(&mut board, &Aliases::default()).unwrap(), 0);
+}
+
+#[test]
+fn copy_atomic() {
+    let mut board = FakeClipboard::default();
+    let mut map = IndexMap::new();
+    map.insert(ContentType::Text, b"a".to_vec());
+    map.insert(ContentType::Html, b"<b>a</b>".to_vec());
+    cliptools::set_verified(&mut board, map, &Aliases::default()).unwrap();
+    assert_eq!(cliptools::get(&mut board, &ContentType::Html).unwrap(), b"<b>a</b>");
+
+    // Both types are stored as text/plain, so one of them can't be read back.
+    let mut map = IndexMap::new();
+    map.insert(ContentType::Text, b"a".to_vec());
+    map.insert(ContentType::Custom("text/plain".into()), b"b".to_vec());
+    let err = cliptools::set_verified(&mut board, map, &Aliases::default()).unwrap_err();
+    assert!(format!("{:#}", err).contains("verification failed"));
+    assert!(matches!(error_of(err), CliptoolsError::InternalError));
+}
+
+#[test]
+fn selftest_restores_contents() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"mine"), ("text/html", b"<b>mine</b>")]);
+    let outcomes = cliptools::selftest::run(&mut board).unwrap();
+    assert_eq!(outcomes, vec![("text", Outcome::Passed), ("png", Outcome::Passed)]);
+    assert_eq!(cliptools::get_text(&mut board).unwrap(), "mine");
+    assert_eq!(cliptools::get(&mut board, &ContentType::Html).unwrap(), b"<b>mine</b>");
+
+    let mut board = FakeClipboard::default();
+    cliptools::selftest::run(&mut board).unwrap();
+    assert_eq!(cliptools::count_types(&mut board, &Aliases::default()).unwrap(), 0);
+}
+
+#[test]
+fn probe_partial_failure() {
+    // UTF8_STRING is listed, but read back as text/plain, which is missing.
+    let mut board = FakeClipboard::with(&[("text/html", b"<b>a</b>"), ("UTF8_STRING", b"a")]);
+    let report = cliptools::probe::probe(&mut board, &Aliases::default());
+    assert_eq!(report["version"], 1);
+    assert_eq!(
+        report["types"][0],
+        serde_json::json!({
+            "type": "html",
+            "system": "text/html",
+            "size": 8,
+        })
+    );
+    assert_eq!(report["types"][1]["type"], "text");
+    assert!(report["types"][1]["error"].is_string());
+    assert!(report["types"][1].get("size").is_none());
+
+    let report = cliptools::probe::probe(&mut FakeClipboard::default(), &Aliases::default());
+    assert_eq!(report["types"], serde_json::json!([]));
+}
+
+#[test]
+fn watch_timeout() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"same")]);
+    let timeout = Some(std::time::Duration::from_millis(0));
+    let err = cliptools::watch::wait_for_change(&mut board, None, timeout).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::Timeout));
+}
+
+#[test]
+fn watch_since() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"new")]);
+    let timeout = Some(std::time::Duration::from_secs(5));
+    let unknown = "0".repeat(64);
+    let mut seen = Vec::new();
+    let err = cliptools::watch::watch(&mut board, None, timeout, Some(&unknown), |val| {
+        seen.push(val.to_vec());
+        Err(CliptoolsError::InternalError.into())
+    })
+    .unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::InternalError));
+    assert_eq!(seen, vec![b"new".to_vec()]);
+
+    let since = cliptools::hash::hex_digest(cliptools::hash::HashAlgorithm::Md5, b"new");
+    let timeout = Some(std::time::Duration::from_millis(300));
+    let err = cliptools::watch::watch(&mut board, None, timeout, Some(&since), |_| {
+        panic!("no change expected")
+    })
+    .unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::Timeout));
+}
+
+#[test]
+fn copy_from_selection() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"a"), ("text/html", b"<b>a</b>")]);
+    let all = cliptools::get_all(&mut board).unwrap();
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[&ContentType::Html], b"<b>a</b>");
+
+    // The fake clipboard, like most backends, has no primary selection.
+    let err = cliptools::copy_selection(&mut board, Selection::Primary, Selection::Clipboard)
+        .unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::ArgumentError(_)));
+}
+
+#[test]
+fn swap() {
+    let mut board = FakeClipboard::with_primary(
+        &[("text/plain", b"a"), ("text/html", b"<b>a</b>")],
+        &[("text/plain", b"b")],
+    );
+    cliptools::swap_selections(&mut board).unwrap();
+    assert_eq!(cliptools::get_text(&mut board).unwrap(), "b");
+    cliptools::select(&mut board, Selection::Primary).unwrap();
+    assert_eq!(cliptools::get_text(&mut board).unwrap(), "a");
+    assert_eq!(cliptools::get(&mut board, &ContentType::Html).unwrap(), b"<b>a</b>");
+}
+
+#[test]
+fn swap_without_primary() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"a")]);
+    let err = cliptools::swap_selections(&mut board).unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::ArgumentError(_)));
+    assert_eq!(cliptools::get_text(&mut board).unwrap(), "a");
+}
+
+#[test]
+fn copy_between_selections() {
+    let mut board = FakeClipboard::with_primary(&[("text/plain", b"a")], &[("text/plain", b"b")]);
+    cliptools::copy_selection(&mut board, Selection::Primary, Selection::Clipboard).unwrap();
+    assert_eq!(cliptools::get_text(&mut board).unwrap(), "b");
+
+    let mut board = FakeClipboard::with_primary(&[("text/html", b"<b>a</b>")], &[]);
+    cliptools::copy_selection(&mut board, Selection::Clipboard, Selection::Primary).unwrap();
+    assert_eq!(cliptools::get(&mut board, &ContentType::Html).unwrap(), b"<b>a</b>");
+    cliptools::select(&mut board, Selection::Clipboard).unwrap();
+    let err = cliptools::copy_selection(&mut board, Selection::Secondary, Selection::Clipboard)
+        .unwrap_err();
+    assert!(matches!(error_of(err), CliptoolsError::ArgumentError(_)));
+}
+
+#[test]
+fn clear() {
+    let mut board = FakeClipboard::with(&[("text/plain", b"a"), ("text/html", b"<b>a</b>")]);
+    cliptools::clear(&mut board).unwrap();
+    assert!(cliptools::list(&mut board, &Aliases::default(), true, None, true).unwrap().is_empty());
+}